@@ -0,0 +1,338 @@
+//! Keeps the attempt in progress on disk, so it isn't lost if the timer
+//! crashes. The files are removed whenever no attempt is in progress, so if
+//! they still exist on the next start, the timer exited during an attempt.
+//!
+//! Alongside the state of the attempt, the splits are stored with the attempt
+//! already recorded in their history, exactly as resetting would record it.
+
+use std::{fs, io, path::PathBuf, sync::mpsc, thread, time::Duration};
+
+use chrono::{DateTime, Local};
+use livesplit_core::{
+    run::saver::livesplit::{save_run, IoWrite},
+    Run, TimeSpan, Timer, TimerPhase,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, Config};
+
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Deserialize)]
+pub struct Attempt {
+    /// The splits the attempt was made with.
+    pub splits: Option<PathBuf>,
+    pub game: String,
+    pub category: String,
+    pub started: DateTime<Local>,
+    pub current_split_index: usize,
+    /// The split times of the segments that were already completed. Skipped
+    /// segments have no times.
+    pub split_times: Vec<SplitTime>,
+    pub real_time: f64,
+    pub game_time: Option<f64>,
+    pub paused: bool,
+    pub game_time_paused: bool,
+    /// The splits with the attempt recorded in their history. They are loaded
+    /// right away, as the file is overwritten by the next attempt.
+    #[serde(skip)]
+    pub recorded_splits: Option<Run>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SplitTime {
+    pub real_time: Option<f64>,
+    pub game_time: Option<f64>,
+}
+
+/// The offsets of the splits before and after restoring an attempt. The timer
+/// can only be started at the attempt's time through the offset, so it needs
+/// to be put back once the attempt is over, and whenever the splits are saved
+/// before that.
+#[derive(Copy, Clone)]
+pub struct RestoredOffset {
+    original: TimeSpan,
+    restored: TimeSpan,
+}
+
+fn state_path() -> Option<PathBuf> {
+    Config::path("attempt.yml")
+}
+
+fn recorded_splits_path() -> Option<PathBuf> {
+    Config::path("attempt.lss")
+}
+
+/// Writes the attempt in progress on a thread of its own, so the timer isn't
+/// held up by the disk. Everything is written in the order it was requested,
+/// so removing the files is never undone by an earlier save.
+pub struct Saver {
+    jobs: Option<mpsc::Sender<Job>>,
+    thread: Option<thread::JoinHandle<()>>,
+    /// The splits, attempt and split the recorded splits were last taken for.
+    recorded_for: Option<(Option<PathBuf>, u32, Option<usize>)>,
+}
+
+enum Job {
+    Save {
+        attempt: Attempt,
+        recorded: Option<Run>,
+    },
+    Remove,
+}
+
+impl Saver {
+    pub fn new() -> Self {
+        let (jobs, receiver) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name(String::from("Attempt Recovery"))
+            .spawn(move || {
+                for job in receiver {
+                    match job {
+                        Job::Save { attempt, recorded } => {
+                            if let Err(e) = write(&attempt, recorded.as_ref()) {
+                                log::error!("Failed to save the attempt in progress: {}", e);
+                            }
+                        }
+                        Job::Remove => remove(),
+                    }
+                }
+            });
+        let thread = match thread {
+            Ok(thread) => Some(thread),
+            Err(e) => {
+                log::error!("Failed to start saving attempts in progress: {}", e);
+                None
+            }
+        };
+        Self {
+            jobs: thread.as_ref().map(|_| jobs),
+            thread,
+            recorded_for: None,
+        }
+    }
+
+    /// Takes the state of the attempt in progress to be written. Recording the
+    /// attempt in the splits is slow for splits with a long history, so they
+    /// are only taken again once another split is reached.
+    pub fn save(
+        &mut self,
+        timer: &Timer,
+        splits: Option<PathBuf>,
+        restored_offset: Option<RestoredOffset>,
+    ) {
+        let recorded_for = Some((
+            splits.clone(),
+            timer.run().attempt_count(),
+            timer.current_split_index(),
+        ));
+        let recorded = if self.recorded_for != recorded_for {
+            self.recorded_for = recorded_for;
+            Some(run_to_save(timer, restored_offset))
+        } else {
+            None
+        };
+        self.send(Job::Save {
+            attempt: Attempt::of(timer, splits),
+            recorded,
+        });
+    }
+
+    /// Removes the attempt from disk once it is over.
+    pub fn remove(&mut self) {
+        self.recorded_for = None;
+        self.send(Job::Remove);
+    }
+
+    fn send(&self, job: Job) {
+        if let Some(jobs) = &self.jobs {
+            if jobs.send(job).is_err() {
+                log::error!("Saving attempts in progress stopped unexpectedly");
+            }
+        }
+    }
+}
+
+impl Drop for Saver {
+    fn drop(&mut self) {
+        // Everything requested is still written before exiting.
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Writes the attempt to disk, along with the splits with the attempt
+/// recorded, if they were taken again.
+fn write(attempt: &Attempt, recorded: Option<&Run>) -> io::Result<()> {
+    let (state_path, recorded_splits_path) = match (state_path(), recorded_splits_path()) {
+        (Some(state), Some(splits)) => (state, splits),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No configuration directory",
+            ))
+        }
+    };
+    if let Some(parent) = state_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // The splits are written first, so the state never refers to an attempt
+    // that isn't recorded yet.
+    if let Some(recorded) = recorded {
+        config::write_atomically(&recorded_splits_path, |file| {
+            save_run(recorded, IoWrite(file))
+                .map_err(|_| io::Error::new(io::ErrorKind::Other, "Failed to write the splits"))
+        })?;
+    }
+    config::write_atomically(&state_path, |file| {
+        serde_yaml::to_writer(file, attempt).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    })
+}
+
+/// The timer's splits with the attempt in progress recorded, the way they are
+/// saved. The offset of a restored attempt isn't saved with them.
+pub fn run_to_save(timer: &Timer, restored_offset: Option<RestoredOffset>) -> Run {
+    let mut run = timer.clone().into_run(true);
+    if let Some(offset) = restored_offset {
+        if run.offset() == offset.restored {
+            run.set_offset(offset.original);
+        }
+    }
+    run
+}
+
+/// Loads the attempt that was in progress when the timer last exited, if
+/// there was one.
+pub fn load() -> Option<Attempt> {
+    let file = fs::File::open(state_path()?).ok()?;
+    let mut attempt: Attempt = match serde_yaml::from_reader(io::BufReader::new(file)) {
+        Ok(attempt) => attempt,
+        Err(e) => {
+            log::error!("Failed to load the attempt to recover: {}", e);
+            return None;
+        }
+    };
+    attempt.recorded_splits = match Config::parse_run_from(&recorded_splits_path()?) {
+        Ok(run) => Some(run),
+        Err(e) => {
+            log::error!("Failed to load the splits of the attempt to recover: {}", e);
+            None
+        }
+    };
+    Some(attempt)
+}
+
+/// Removes the attempt from disk, once it is over or was dealt with.
+fn remove() {
+    for path in [state_path(), recorded_splits_path()].iter().flatten() {
+        if let Err(e) = fs::remove_file(path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                log::warn!("Failed to remove {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+impl Attempt {
+    fn of(timer: &Timer, splits: Option<PathBuf>) -> Self {
+        let run = timer.run();
+        let completed = timer
+            .current_split_index()
+            .unwrap_or_default()
+            .min(run.len());
+        let current_time = timer.current_time();
+        let real_time = current_time.real_time.unwrap_or_default();
+        Self {
+            splits,
+            game: run.game_name().to_owned(),
+            category: run.category_name().to_owned(),
+            started: Local::now()
+                - chrono::Duration::milliseconds(
+                    timer.current_attempt_duration().total_milliseconds() as i64,
+                ),
+            current_split_index: completed,
+            split_times: run.segments()[..completed]
+                .iter()
+                .map(|segment| {
+                    let split_time = segment.split_time();
+                    SplitTime {
+                        real_time: split_time.real_time.map(|time| time.total_seconds()),
+                        game_time: split_time.game_time.map(|time| time.total_seconds()),
+                    }
+                })
+                .collect(),
+            real_time: real_time.total_seconds(),
+            game_time: current_time
+                .game_time
+                .filter(|_| timer.is_game_time_initialized())
+                .map(|time| time.total_seconds()),
+            paused: timer.current_phase() == TimerPhase::Paused,
+            game_time_paused: timer.is_game_time_paused(),
+            recorded_splits: None,
+        }
+    }
+
+    /// Continues the attempt in the timer, which needs to use the splits the
+    /// attempt was made with. The timer can't go back in time, so the splits
+    /// before the last completed one are skipped, and the last one is split at
+    /// the time the attempt was saved. That way no segment is recorded with a
+    /// time that is too short.
+    pub fn restore(&self, timer: &mut Timer) -> Option<RestoredOffset> {
+        let mut run = timer.run().clone();
+        let original = run.offset();
+        let restored = TimeSpan::from_seconds(self.real_time);
+        run.set_offset(restored);
+        timer.set_run(run).ok()?;
+
+        timer.start();
+        if self.game_time.is_some() {
+            timer.initialize_game_time();
+        }
+        let completed = self.split_times.len().min(timer.run().len());
+        for _ in 1..completed {
+            timer.skip_split();
+        }
+        if let Some(last) = self.split_times[..completed].last() {
+            if last.real_time.is_some() || last.game_time.is_some() {
+                if let Some(game_time) = last.game_time {
+                    timer.set_game_time(TimeSpan::from_seconds(game_time));
+                }
+                timer.split();
+            } else {
+                timer.skip_split();
+            }
+        }
+
+        if let Some(game_time) = self.game_time {
+            timer.set_game_time(TimeSpan::from_seconds(game_time));
+        }
+        if self.game_time_paused {
+            timer.pause_game_time();
+        }
+        if self.paused {
+            timer.pause();
+        }
+
+        Some(RestoredOffset { original, restored })
+    }
+}
+
+impl RestoredOffset {
+    /// Puts the original offset back once the restored attempt is over, and
+    /// returns whether it is. If different splits were opened in the
+    /// meantime, there is nothing to put back.
+    pub fn put_back(&self, timer: &mut Timer) -> bool {
+        if timer.current_phase() != TimerPhase::NotRunning {
+            return false;
+        }
+        if timer.run().offset() == self.restored {
+            let mut run = timer.run().clone();
+            run.set_offset(self.original);
+            let _ = timer.set_run(run);
+        }
+        true
+    }
+}
@@ -0,0 +1,332 @@
+//! A window that shows what the auto splitter is doing. It shows whether the
+//! auto splitter is loaded and its log messages, which are collected as they
+//! are printed.
+//!
+//! This only covers part of what was asked for. The runtime is handed the
+//! timer itself and doesn't expose the process the auto splitter is attached
+//! to, its tick rate or its variables, so none of these can be shown. The
+//! actions it performs can't be seen where they are issued either. Instead
+//! the timer is watched while the window is open, and its changes are listed
+//! no matter whether they came from the auto splitter, a hotkey or the
+//! server. Changes that happen in quick succession may show up as a single
+//! one.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt::Write,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use druid::{
+    widget::{Controller, CrossAxisAlignment, Flex, Label, LineBreaking, Scroll},
+    Data, Env, Event, EventCtx, Lens, LifeCycle, LifeCycleCtx, TimerToken, Widget, WidgetExt,
+};
+use livesplit_core::{
+    timing::formatter::{Regular, TimeFormatter},
+    SharedTimer, TimeSpan, TimerPhase,
+};
+
+use crate::{
+    config::Config,
+    consts::{COLUMN_LABEL_FONT, MARGIN, SPACING},
+};
+
+/// The log target the auto splitting runtime uses for the messages printed by
+/// the auto splitter.
+pub const LOG_TARGET: &str = "Auto Splitter";
+
+const MAX_ENTRIES: usize = 200;
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Collects the auto splitter's log messages and the changes to the timer, so
+/// they can be shown in the debug window. This is shared with the
+/// logger, which may run on any thread.
+#[derive(Clone, Default)]
+pub struct AutoSplitterLog(Arc<Mutex<Entries>>);
+
+#[derive(Default)]
+struct Entries {
+    messages: VecDeque<String>,
+    timer_changes: VecDeque<String>,
+    generation: u64,
+}
+
+impl AutoSplitterLog {
+    pub fn push_message(&self, message: String) {
+        if let Ok(mut entries) = self.0.lock() {
+            push_bounded(&mut entries.messages, message);
+            entries.generation += 1;
+        }
+    }
+
+    fn push_timer_change(&self, change: String) {
+        if let Ok(mut entries) = self.0.lock() {
+            push_bounded(
+                &mut entries.timer_changes,
+                format!("{} {}", chrono::Local::now().format("[%H:%M:%S]"), change),
+            );
+            entries.generation += 1;
+        }
+    }
+}
+
+fn push_bounded(entries: &mut VecDeque<String>, entry: String) {
+    if entries.len() == MAX_ENTRIES {
+        entries.pop_front();
+    }
+    entries.push_back(entry);
+}
+
+#[derive(Clone, Data, Lens)]
+pub struct State {
+    script: String,
+    timer_changes: String,
+    messages: String,
+    #[data(ignore)]
+    timer: SharedTimer,
+    #[data(ignore)]
+    log: AutoSplitterLog,
+    #[data(ignore)]
+    config: Rc<RefCell<Config>>,
+    #[data(ignore)]
+    generation: u64,
+    #[data(ignore)]
+    observed: Option<Observed>,
+}
+
+impl State {
+    pub fn new(timer: SharedTimer, log: AutoSplitterLog, config: Rc<RefCell<Config>>) -> Self {
+        let mut state = Self {
+            script: String::new(),
+            timer_changes: String::new(),
+            messages: String::new(),
+            timer,
+            log,
+            config,
+            generation: u64::MAX,
+            observed: None,
+        };
+        state.refresh();
+        state
+    }
+
+    fn refresh(&mut self) {
+        self.observe_timer();
+
+        let config = self.config.borrow();
+        let status = config.auto_splitter_status();
+        let mut script = match &status.loaded {
+            Some(path) => format!("Loaded {}", path.display()),
+            None => String::from("No auto splitter loaded"),
+        };
+        if let Some(error) = &status.error {
+            script.push('\n');
+            script.push_str(error);
+        }
+        drop(config);
+        if self.script != script {
+            self.script = script;
+        }
+
+        let entries = match self.log.0.lock() {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        if entries.generation != self.generation {
+            self.generation = entries.generation;
+            self.timer_changes = join_lines(&entries.timer_changes);
+            self.messages = join_lines(&entries.messages);
+        }
+    }
+
+    fn observe_timer(&mut self) {
+        let observed = match self.timer.read() {
+            Ok(timer) => Observed::new(&timer),
+            Err(_) => return,
+        };
+        if let Some(previous) = self.observed.replace(observed) {
+            previous.report_changes(&observed, &self.log);
+        }
+    }
+}
+
+fn join_lines(entries: &VecDeque<String>) -> String {
+    let mut text = String::new();
+    for entry in entries.iter().rev() {
+        text.push_str(entry);
+        text.push('\n');
+    }
+    text
+}
+
+/// The parts of the timer's state an auto splitter can change. The changes are
+/// named after the action that most likely caused them.
+#[derive(Copy, Clone, PartialEq)]
+struct Observed {
+    phase: TimerPhase,
+    split_index: Option<usize>,
+    skipped_last: bool,
+    game_time_paused: bool,
+    loading_times: TimeSpan,
+    game_time: Option<TimeSpan>,
+}
+
+impl Observed {
+    fn new(timer: &livesplit_core::Timer) -> Self {
+        let split_index = timer.current_split_index();
+        let skipped_last = split_index
+            .and_then(|index| index.checked_sub(1))
+            .and_then(|index| timer.run().segments().get(index))
+            .map_or(false, |segment| {
+                segment
+                    .split_time()
+                    .real_time
+                    .or(segment.split_time().game_time)
+                    .is_none()
+            });
+
+        Self {
+            phase: timer.current_phase(),
+            split_index,
+            skipped_last,
+            game_time_paused: timer.is_game_time_paused(),
+            loading_times: timer.loading_times(),
+            game_time: timer.current_time().game_time,
+        }
+    }
+
+    fn report_changes(&self, new: &Self, log: &AutoSplitterLog) {
+        if self == new {
+            return;
+        }
+
+        match (self.phase, new.phase) {
+            (TimerPhase::NotRunning, TimerPhase::Running) => log.push_timer_change("Start".into()),
+            (_, TimerPhase::NotRunning) if self.phase != TimerPhase::NotRunning => {
+                log.push_timer_change("Reset".into())
+            }
+            (TimerPhase::Running, TimerPhase::Paused) => log.push_timer_change("Pause".into()),
+            (TimerPhase::Paused, TimerPhase::Running) => log.push_timer_change("Resume".into()),
+            _ => {}
+        }
+
+        if new.phase != TimerPhase::NotRunning && self.phase != TimerPhase::NotRunning {
+            match (self.split_index, new.split_index) {
+                (Some(old), Some(index)) if index > old => {
+                    log.push_timer_change(if new.skipped_last {
+                        "Skip Split".into()
+                    } else {
+                        "Split".into()
+                    })
+                }
+                (Some(old), Some(index)) if index < old => {
+                    log.push_timer_change("Undo Split".into())
+                }
+                _ => {}
+            }
+        }
+
+        if self.game_time_paused != new.game_time_paused {
+            log.push_timer_change(if new.game_time_paused {
+                "Pause Game Time".into()
+            } else {
+                "Resume Game Time".into()
+            });
+        }
+
+        if self.loading_times != new.loading_times {
+            let mut change = String::from("Set Loading Times to ");
+            let _ = write!(change, "{}", Regular::new().format(new.loading_times));
+            log.push_timer_change(change);
+        }
+
+        // While the game time is paused, it only changes when it is set.
+        if self.game_time_paused && new.game_time_paused && self.game_time != new.game_time {
+            let mut change = String::from("Set Game Time to ");
+            let _ = write!(change, "{}", Regular::new().format(new.game_time));
+            log.push_timer_change(change);
+        }
+    }
+}
+
+struct RefreshController {
+    timer_token: TimerToken,
+}
+
+impl<W: Widget<State>> Controller<State, W> for RefreshController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut State,
+        env: &Env,
+    ) {
+        if let Event::Timer(token) = event {
+            if *token == self.timer_token {
+                data.refresh();
+                self.timer_token = ctx.request_timer(REFRESH_INTERVAL);
+            }
+        }
+        child.event(ctx, event, data, env)
+    }
+
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &State,
+        env: &Env,
+    ) {
+        if let LifeCycle::WidgetAdded = event {
+            self.timer_token = ctx.request_timer(REFRESH_INTERVAL);
+        }
+        child.lifecycle(ctx, event, data, env)
+    }
+}
+
+fn section(title: &str, lens: impl Lens<State, String> + 'static) -> impl Widget<State> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(title).with_font(COLUMN_LABEL_FONT))
+        .with_spacer(SPACING / 2.0)
+        .with_child(
+            Label::new(|text: &String, _: &Env| text.clone())
+                .with_line_break_mode(LineBreaking::WordWrap)
+                .lens(lens),
+        )
+}
+
+pub fn root_widget() -> impl Widget<State> {
+    Scroll::new(
+        Flex::column()
+            .cross_axis_alignment(CrossAxisAlignment::Start)
+            .with_child(section("Script", State::script))
+            .with_spacer(SPACING)
+            .with_child(section(
+                "Timer Changes (from any source)",
+                State::timer_changes,
+            ))
+            .with_spacer(SPACING)
+            .with_child(section("Log", State::messages))
+            .with_spacer(SPACING)
+            .with_child(
+                Label::new(
+                    "The attached process, the tick rate and which actions the auto \
+                    splitter sent can't be shown, as the runtime doesn't report them.",
+                )
+                .with_line_break_mode(LineBreaking::WordWrap),
+            )
+            .padding(MARGIN)
+            .expand_width(),
+    )
+    .vertical()
+    .expand_height()
+    .controller(RefreshController {
+        timer_token: TimerToken::INVALID,
+    })
+}
@@ -0,0 +1,419 @@
+use std::{cell::RefCell, collections::BTreeMap, fmt::Write, rc::Rc};
+
+use druid::{
+    commands,
+    widget::{Button, Flex, ListIter, Scroll},
+    Data, Widget, WidgetExt,
+};
+use livesplit_core::{
+    auto_splitting::{self, settings},
+    settings::Value,
+    Run,
+};
+
+use crate::{
+    consts::{BUTTON_SPACING, DIALOG_BUTTON_HEIGHT, DIALOG_BUTTON_WIDTH, MARGIN},
+    settings_table::{self, ChoiceOption, RowKind, SettingsRow},
+};
+
+/// The values the user chose for the settings an auto splitter exposes, keyed
+/// by the setting's key.
+pub type AutoSplitterSettings = BTreeMap<String, AutoSplitterSetting>;
+
+#[derive(Clone, PartialEq)]
+pub enum AutoSplitterSetting {
+    Bool(bool),
+    String(String),
+}
+
+/// Reads the settings stored in the splits. They are stored the way LiveSplit
+/// stores the custom settings of its auto splitters, so that the rest of the
+/// auto splitter settings in the splits are kept.
+pub fn read(run: &Run) -> AutoSplitterSettings {
+    let xml = run.auto_splitter_settings();
+    let mut settings = AutoSplitterSettings::new();
+    let mut rest = match custom_settings(xml) {
+        Some((start, end)) => &xml[start..end],
+        None => return settings,
+    };
+
+    while let Some(start) = rest.find("<Setting ") {
+        rest = &rest[start + "<Setting ".len()..];
+        let tag_end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[..tag_end];
+        rest = &rest[tag_end + 1..];
+        let content = if tag.ends_with('/') {
+            ""
+        } else {
+            let end = rest.find("</Setting>").unwrap_or(rest.len());
+            &rest[..end]
+        };
+
+        let key = match attribute(tag, "id") {
+            Some(key) => key,
+            None => continue,
+        };
+        let value = match attribute(tag, "type").as_deref() {
+            Some("bool") => AutoSplitterSetting::Bool(content.trim() == "True"),
+            Some("string") => AutoSplitterSetting::String(
+                attribute(tag, "value").unwrap_or_else(|| unescape(content)),
+            ),
+            _ => continue,
+        };
+        settings.insert(key, value);
+    }
+    settings
+}
+
+/// Stores the settings in the splits, replacing the ones stored before.
+pub fn write(run: &mut Run, settings: &AutoSplitterSettings) {
+    let mut custom_settings = String::from("<CustomSettings>");
+    for (key, value) in settings {
+        let _ = match value {
+            AutoSplitterSetting::Bool(v) => write!(
+                custom_settings,
+                r#"<Setting id="{}" type="bool">{}</Setting>"#,
+                escape(key),
+                if *v { "True" } else { "False" },
+            ),
+            AutoSplitterSetting::String(v) => write!(
+                custom_settings,
+                r#"<Setting id="{}" type="string" value="{}" />"#,
+                escape(key),
+                escape(v),
+            ),
+        };
+    }
+    custom_settings.push_str("</CustomSettings>");
+
+    let xml = run.auto_splitter_settings_mut();
+    match custom_settings_range(xml) {
+        Some((start, end)) => xml.replace_range(start..end, &custom_settings),
+        None => xml.push_str(&custom_settings),
+    }
+}
+
+/// Passes the settings on to the loaded auto splitter. Settings the user never
+/// changed keep the default the auto splitter declared.
+pub fn apply(runtime: &auto_splitting::Runtime, settings: &AutoSplitterSettings) {
+    let mut map = runtime.settings_map().unwrap_or_default();
+    for (key, value) in settings {
+        let value = match value {
+            AutoSplitterSetting::Bool(v) => settings::Value::Bool(*v),
+            AutoSplitterSetting::String(v) => settings::Value::String(v.as_str().into()),
+        };
+        map.insert(key.as_str().into(), value);
+    }
+    runtime.set_settings_map(map);
+}
+
+/// The content of the `CustomSettings` element.
+fn custom_settings(xml: &str) -> Option<(usize, usize)> {
+    let (start, end) = custom_settings_range(xml)?;
+    let element = &xml[start..end];
+    let content_start = start + element.find('>')? + 1;
+    let content_end = if element.ends_with("/>") {
+        content_start
+    } else {
+        end - "</CustomSettings>".len()
+    };
+    Some((content_start, content_end.max(content_start)))
+}
+
+/// The whole `CustomSettings` element, including its tags.
+fn custom_settings_range(xml: &str) -> Option<(usize, usize)> {
+    let start = xml.find("<CustomSettings")?;
+    let rest = &xml[start..];
+    let end = match rest.find("</CustomSettings>") {
+        Some(end) => end + "</CustomSettings>".len(),
+        None => rest.find("/>")? + "/>".len(),
+    };
+    Some((start, start + end))
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!("{}=\"", name);
+    let start = tag
+        .match_indices(&pattern)
+        .find(|&(index, _)| index == 0 || tag[..index].ends_with(char::is_whitespace))?
+        .0
+        + pattern.len();
+    let end = start + tag[start..].find('"')?;
+    Some(unescape(&tag[start..end]))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[derive(Clone, Data)]
+pub struct State {
+    rows: Rc<Vec<SettingsRow>>,
+    #[data(ignore)]
+    keys: Rc<Vec<String>>,
+    #[data(ignore)]
+    pub editor: Rc<RefCell<Option<AutoSplitterSettings>>>,
+    #[data(ignore)]
+    pub closed_with_ok: bool,
+}
+
+impl State {
+    pub(crate) fn new(widgets: &[settings::Widget], editor: AutoSplitterSettings) -> Self {
+        let mut rows = Vec::with_capacity(widgets.len());
+        let mut keys = Vec::with_capacity(widgets.len());
+
+        for widget in widgets {
+            let key = &*widget.key;
+            let (value, kind) = match &widget.kind {
+                settings::WidgetKind::Title { .. } => (Value::Bool(false), RowKind::Title),
+                settings::WidgetKind::Bool { default_value } => {
+                    let value = match editor.get(key) {
+                        Some(AutoSplitterSetting::Bool(v)) => *v,
+                        _ => *default_value,
+                    };
+                    (Value::Bool(value), RowKind::Value)
+                }
+                // A choice without options has nothing to choose from.
+                settings::WidgetKind::Choice { options, .. } if options.is_empty() => continue,
+                settings::WidgetKind::Choice {
+                    default_option_key,
+                    options,
+                } => {
+                    let value = match editor.get(key) {
+                        Some(AutoSplitterSetting::String(v)) => v.clone(),
+                        _ => default_option_key.to_string(),
+                    };
+                    let options = options
+                        .iter()
+                        .map(|option| ChoiceOption {
+                            key: option.key.to_string(),
+                            description: option.description.to_string(),
+                        })
+                        .collect();
+                    (Value::String(value), RowKind::Choice(options))
+                }
+                // Settings we don't know how to edit are left at their default.
+                _ => continue,
+            };
+
+            rows.push(SettingsRow {
+                index: rows.len(),
+                text: widget.description.to_string(),
+                value,
+                kind,
+            });
+            keys.push(key.to_owned());
+        }
+
+        Self {
+            rows: Rc::new(rows),
+            keys: Rc::new(keys),
+            editor: Rc::new(RefCell::new(Some(editor))),
+            closed_with_ok: false,
+        }
+    }
+}
+
+impl ListIter<SettingsRow> for State {
+    fn for_each(&self, mut cb: impl FnMut(&SettingsRow, usize)) {
+        for (index, row) in self.rows.iter().enumerate() {
+            cb(row, index);
+        }
+    }
+
+    fn for_each_mut(&mut self, mut cb: impl FnMut(&mut SettingsRow, usize)) {
+        let mut editor = self.editor.borrow_mut();
+        let editor = editor.as_mut().unwrap();
+        let mut changed_rows = None;
+
+        for (index, field) in self.rows.iter().enumerate() {
+            let mut row = field.clone();
+            cb(&mut row, index);
+            if row.value != field.value {
+                let value = match &row.value {
+                    Value::Bool(v) => AutoSplitterSetting::Bool(*v),
+                    Value::String(v) => AutoSplitterSetting::String(v.clone()),
+                    _ => continue,
+                };
+                editor.insert(self.keys[index].clone(), value);
+                changed_rows.get_or_insert_with(|| (*self.rows).clone())[index] = row;
+            }
+        }
+
+        if let Some(rows) = changed_rows {
+            self.rows = Rc::new(rows);
+        }
+    }
+
+    fn data_len(&self) -> usize {
+        self.rows.len()
+    }
+}
+
+pub fn root_widget() -> impl Widget<State> {
+    Flex::column()
+        .with_flex_child(settings_editor(), 1.0)
+        .with_child(dialog_buttons())
+}
+
+fn settings_editor() -> impl Widget<State> {
+    Scroll::new(settings_table::widget().padding(MARGIN))
+        .vertical()
+        .expand_height()
+}
+
+fn dialog_buttons() -> impl Widget<State> {
+    Flex::row()
+        .with_flex_spacer(1.0)
+        .with_child(
+            Button::new("OK")
+                .on_click(|ctx, state: &mut State, _| {
+                    state.closed_with_ok = true;
+                    ctx.submit_command(commands::CLOSE_WINDOW);
+                })
+                .fix_size(DIALOG_BUTTON_WIDTH, DIALOG_BUTTON_HEIGHT),
+        )
+        .with_spacer(BUTTON_SPACING)
+        .with_child(
+            Button::new("Cancel")
+                .on_click(|ctx, _, _| {
+                    ctx.submit_command(commands::CLOSE_WINDOW);
+                })
+                .fix_size(DIALOG_BUTTON_WIDTH, DIALOG_BUTTON_HEIGHT),
+        )
+        .padding(MARGIN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with(xml: &str) -> Run {
+        let mut run = Run::new();
+        run.auto_splitter_settings_mut().push_str(xml);
+        run
+    }
+
+    fn settings_of(entries: &[(&str, AutoSplitterSetting)]) -> AutoSplitterSettings {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let settings = settings_of(&[
+            ("split_on_boss", AutoSplitterSetting::Bool(true)),
+            ("start_on_load", AutoSplitterSetting::Bool(false)),
+            ("route", AutoSplitterSetting::String(String::from("any%"))),
+        ]);
+        let mut run = Run::new();
+        write(&mut run, &settings);
+        assert!(read(&run) == settings);
+
+        // Writing again replaces the settings instead of adding to them.
+        let changed = settings_of(&[("route", AutoSplitterSetting::String(String::new()))]);
+        write(&mut run, &changed);
+        assert!(read(&run) == changed);
+        assert_eq!(
+            run.auto_splitter_settings()
+                .matches("<CustomSettings>")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn self_closing_custom_settings() {
+        let mut run = run_with("<CustomSettings/>");
+        assert!(read(&run).is_empty());
+
+        let settings = settings_of(&[("split", AutoSplitterSetting::Bool(true))]);
+        write(&mut run, &settings);
+        assert!(read(&run) == settings);
+        assert!(!run.auto_splitter_settings().contains("<CustomSettings/>"));
+
+        let run = run_with(r#"<CustomSettings /><Setting id="outside" type="bool">True</Setting>"#);
+        assert!(read(&run).is_empty());
+    }
+
+    #[test]
+    fn escaped_values() {
+        let settings = settings_of(&[
+            (
+                r#"a&b<"c">"#,
+                AutoSplitterSetting::String(String::from(r#"<x> & "y""#)),
+            ),
+            ("plain", AutoSplitterSetting::Bool(true)),
+        ]);
+        let mut run = Run::new();
+        write(&mut run, &settings);
+        assert!(!run.auto_splitter_settings().contains("<x>"));
+        assert!(read(&run) == settings);
+
+        let run = run_with(
+            r#"<CustomSettings><Setting id="it&apos;s" type="string" value="&lt;&amp;&gt;" /></CustomSettings>"#,
+        );
+        assert!(
+            read(&run)
+                == settings_of(&[("it's", AutoSplitterSetting::String(String::from("<&>")))])
+        );
+    }
+
+    #[test]
+    fn reads_livesplit_settings() {
+        let run = run_with(
+            r#"<Version>1.5</Version>
+<CustomSettings>
+  <Setting id="any" type="bool">True</Setting>
+  <Setting id="glitched" type="bool">False</Setting>
+  <Setting id="unknown" type="int">3</Setting>
+</CustomSettings>"#,
+        );
+        assert!(
+            read(&run)
+                == settings_of(&[
+                    ("any", AutoSplitterSetting::Bool(true)),
+                    ("glitched", AutoSplitterSetting::Bool(false)),
+                ])
+        );
+    }
+
+    #[test]
+    fn preserves_surrounding_settings() {
+        let before = "<Version>1.5</Version><AutoReset>True</AutoReset>";
+        let after = "<AutoStart>False</AutoStart>";
+        let mut run = run_with(&format!(
+            r#"{}<CustomSettings><Setting id="old" type="bool">True</Setting></CustomSettings>{}"#,
+            before, after,
+        ));
+        let settings = settings_of(&[("new", AutoSplitterSetting::Bool(false))]);
+        write(&mut run, &settings);
+
+        let xml = run.auto_splitter_settings();
+        assert!(xml.starts_with(before));
+        assert!(xml.ends_with(after));
+        assert!(read(&run) == settings);
+
+        // Without custom settings, they are added after everything else.
+        let mut run = run_with(before);
+        write(&mut run, &settings);
+        assert!(run.auto_splitter_settings().starts_with(before));
+        assert!(read(&run) == settings);
+    }
+}
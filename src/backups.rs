@@ -0,0 +1,292 @@
+//! Copies of splits and layouts taken right before they are overwritten, so a
+//! bad save can be undone. Each file's backups have their own directory, named
+//! after a hash of the file's path, so files with the same name don't share
+//! backups. The backups are named after the time they were taken and the
+//! original file's name. Whether a backup is of splits or of a layout is known
+//! from the file it's listed for.
+
+use std::{
+    cell::RefCell,
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+    thread,
+};
+
+use chrono::{Duration, NaiveDateTime};
+use druid::{
+    commands,
+    widget::{Button, Controller, CrossAxisAlignment, Flex, Label, LineBreaking, Scroll},
+    Env, Event, EventCtx, ExtEventSink, Selector, Target, Widget, WidgetExt, WindowDesc,
+};
+use livesplit_core::timing::formatter::{Regular, TimeFormatter};
+
+use crate::{
+    config::{Config, FileKind},
+    consts::{BUTTON_SPACING, DIALOG_BUTTON_HEIGHT, DIALOG_BUTTON_WIDTH, MARGIN, SPACING},
+    MainState,
+};
+
+/// Restores the backup at the path into the timer.
+pub const RESTORE_BACKUP: Selector<(FileKind, PathBuf)> = Selector::new("restore-backup");
+/// The description of the splits in a backup, once they have been read.
+const BACKUP_DESCRIBED: Selector<(PathBuf, String)> = Selector::new("backup-described");
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d_%H-%M-%S%.3f";
+/// Holds the path of the file the backups in a directory are of, so they can
+/// be told apart when browsing them.
+const SOURCE_FILE_NAME: &str = "source.txt";
+
+pub fn directory() -> Option<PathBuf> {
+    Config::path("backups")
+}
+
+/// The directory with the backups of the file.
+fn directory_of(path: &Path) -> Option<PathBuf> {
+    // FNV-1a, as the hash needs to stay the same across versions of Rust.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in path.to_string_lossy().bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    Some(directory()?.join(format!("{:016x}", hash)))
+}
+
+/// Copies the file into its backup directory, unless it doesn't exist yet.
+/// Only the most recent backups of each file are kept, but always at least the
+/// one just taken.
+pub fn create(path: &Path, retention: usize) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let directory = directory_of(path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No backup directory"))?;
+    fs::create_dir_all(&directory)?;
+    fs::write(
+        directory.join(SOURCE_FILE_NAME),
+        path.to_string_lossy().as_bytes(),
+    )?;
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not a file"))?
+        .to_string_lossy();
+    // Saving twice within the same millisecond would otherwise overwrite the
+    // first backup.
+    let mut timestamp = chrono::Local::now().naive_local();
+    let backup = loop {
+        let backup = directory.join(format!(
+            "{} {}",
+            timestamp.format(TIMESTAMP_FORMAT),
+            file_name
+        ));
+        if !backup.exists() {
+            break backup;
+        }
+        timestamp += Duration::milliseconds(1);
+    };
+    fs::copy(path, backup)?;
+
+    let mut backups = list_in(&directory)?;
+    // The backups are sorted with the most recent one first.
+    for backup in backups.drain(retention.max(1).min(backups.len())..) {
+        if let Err(e) = fs::remove_file(&backup.path) {
+            log::warn!(
+                "Failed to remove old backup {}: {}",
+                backup.path.display(),
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+struct Backup {
+    path: PathBuf,
+    file_name: String,
+    created: NaiveDateTime,
+}
+
+/// Lists the backups in the directory, with the most recent one first.
+fn list_in(directory: &Path) -> io::Result<Vec<Backup>> {
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name,
+            None => continue,
+        };
+        let (timestamp, original) = match name.split_once(' ') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let created = match NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT) {
+            Ok(created) => created,
+            Err(_) => continue,
+        };
+        backups.push(Backup {
+            file_name: original.to_owned(),
+            path,
+            created,
+        });
+    }
+    backups.sort_unstable_by(|a, b| b.created.cmp(&a.created));
+    Ok(backups)
+}
+
+/// Describes the splits in a backup, so the user can tell which attempt
+/// history it contains.
+fn describe_splits(path: &Path) -> Option<String> {
+    let run = Config::parse_run_from(path).ok()?;
+    let pb = run
+        .segments()
+        .last()
+        .map(|segment| segment.personal_best_split_time())
+        .and_then(|time| time.real_time.or(time.game_time));
+    Some(format!(
+        "{} - {}\n{} attempts, PB {}",
+        run.game_name(),
+        run.category_name(),
+        run.attempt_count(),
+        Regular::new().format(pb),
+    ))
+}
+
+/// A window listing the backups of the current splits and layout. Reading the
+/// splits in the backups may take a while, so they are described in the
+/// background.
+pub fn window(config: &Config, sink: ExtEventSink) -> WindowDesc<MainState> {
+    let mut backups = Vec::new();
+    for kind in [FileKind::Splits, FileKind::Layout] {
+        if let Some(directory) = config.current_file(kind).and_then(directory_of) {
+            backups.extend(
+                list_in(&directory)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|backup| (kind, backup)),
+            );
+        }
+    }
+    backups.sort_unstable_by(|(_, a), (_, b)| b.created.cmp(&a.created));
+
+    let splits: Vec<PathBuf> = backups
+        .iter()
+        .filter(|(kind, _)| matches!(kind, FileKind::Splits))
+        .map(|(_, backup)| backup.path.clone())
+        .collect();
+    thread::spawn(move || {
+        for path in splits {
+            let description =
+                describe_splits(&path).unwrap_or_else(|| String::from("Unreadable splits"));
+            // The window may have been closed already.
+            let _ = sink.submit_command(BACKUP_DESCRIBED, (path, description), Target::Global);
+        }
+    });
+
+    WindowDesc::new(root_widget(backups))
+        .title("Restore Backup")
+        .with_min_size((450.0, 300.0))
+        .window_size((500.0, 450.0))
+}
+
+/// Shows the description of the backup's splits once it has been read.
+struct DescriptionController {
+    path: PathBuf,
+    description: Rc<RefCell<String>>,
+}
+
+impl<W: Widget<MainState>> Controller<MainState, W> for DescriptionController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut MainState,
+        env: &Env,
+    ) {
+        if let Event::Command(command) = event {
+            if let Some((path, description)) = command.get(BACKUP_DESCRIBED) {
+                if *path == self.path {
+                    *self.description.borrow_mut() = description.clone();
+                    ctx.request_update();
+                }
+            }
+        }
+        child.event(ctx, event, data, env)
+    }
+}
+
+fn details(kind: FileKind, path: &Path) -> impl Widget<MainState> {
+    let description = Rc::new(RefCell::new(String::from(match kind {
+        FileKind::Splits => "Reading the splits...",
+        FileKind::Layout => "Layout",
+    })));
+    Label::new({
+        let description = description.clone();
+        move |_: &MainState, _: &Env| description.borrow().clone()
+    })
+    .with_line_break_mode(LineBreaking::WordWrap)
+    .controller(DescriptionController {
+        path: path.to_path_buf(),
+        description,
+    })
+}
+
+fn root_widget(backups: Vec<(FileKind, Backup)>) -> impl Widget<MainState> {
+    let mut list = Flex::column().cross_axis_alignment(CrossAxisAlignment::Start);
+
+    if backups.is_empty() {
+        list.add_child(
+            Label::new(format!(
+                "There are no backups of the current splits or layout. Backups are kept in {}.",
+                directory().unwrap_or_default().display(),
+            ))
+            .with_line_break_mode(LineBreaking::WordWrap),
+        );
+    }
+
+    for (kind, backup) in backups {
+        let text = format!(
+            "{} - {}",
+            backup.created.format("%Y-%m-%d %H:%M:%S"),
+            backup.file_name,
+        );
+        let path = backup.path;
+        list.add_child(
+            Flex::row()
+                .with_flex_child(
+                    Flex::column()
+                        .cross_axis_alignment(CrossAxisAlignment::Start)
+                        .with_child(Label::new(text).with_line_break_mode(LineBreaking::WordWrap))
+                        .with_child(details(kind, &path))
+                        .expand_width(),
+                    1.0,
+                )
+                .with_spacer(BUTTON_SPACING)
+                .with_child(
+                    Button::new("Restore")
+                        .on_click(move |ctx, _, _| {
+                            ctx.submit_command(
+                                RESTORE_BACKUP.with((kind, path.clone())).to(Target::Global),
+                            );
+                            ctx.submit_command(commands::CLOSE_WINDOW);
+                        })
+                        .fix_size(DIALOG_BUTTON_WIDTH, DIALOG_BUTTON_HEIGHT),
+                ),
+        );
+        list.add_spacer(SPACING);
+    }
+
+    Flex::column()
+        .with_flex_child(Scroll::new(list.padding(MARGIN)).vertical().expand(), 1.0)
+        .with_child(
+            Flex::row()
+                .with_flex_spacer(1.0)
+                .with_child(
+                    Button::new("Close")
+                        .on_click(|ctx, _, _| ctx.submit_command(commands::CLOSE_WINDOW))
+                        .fix_size(DIALOG_BUTTON_WIDTH, DIALOG_BUTTON_HEIGHT),
+                )
+                .padding(MARGIN),
+        )
+}
@@ -0,0 +1,204 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use livesplit_core::{layout::LayoutSettings, TimingMethod};
+
+use crate::{config::Config, renderer::Backend};
+
+pub const USAGE: &str = "\
+Usage:
+    livesplit-one-desktop [options] [<splits or layout file>]
+    livesplit-one-desktop render --output <file.png> [options] [render options]
+
+Options:
+    --splits <file>           Opens these splits instead of the configured ones.
+    --layout <file>           Opens this layout instead of the configured one.
+    --config <file>           Uses this configuration file instead of the default.
+    --profile <name>          Switches to this profile, which needs to exist.
+    --timing-method <method>  Either real-time or game-time.
+    --comparison <name>       The comparison to compare against.
+
+    A file passed without an option is opened as splits or as a layout,
+    depending on its contents. Apart from the profile, none of these options
+    are saved.
+
+Render options:
+    --output <file.png>       Where to write the rendered image.
+    --width <pixels>          The width of the image. Defaults to the window width.
+    --height <pixels>         The height of the image. Defaults to the window height.
+    --simulate <splits>       Simulates an attempt that is this many splits in,
+                              using the personal best's split times as game time.
+    --renderer <renderer>     Either software or piet. Defaults to the configured one.
+    --compare <file.png>      Also renders with the other renderer, writes that
+                              image here and prints how much the two differ.
+
+    The piet renderer, and with it --compare, is only available when built
+    with the piet-rendering feature.
+";
+
+pub enum Command {
+    Launch(Options),
+    Render(Options, RenderOptions),
+}
+
+/// Options that override the configuration for this session only.
+#[derive(Default)]
+pub struct Options {
+    pub config: Option<PathBuf>,
+    pub profile: Option<String>,
+    pub splits: Option<PathBuf>,
+    pub layout: Option<PathBuf>,
+    pub timing_method: Option<TimingMethod>,
+    pub comparison: Option<String>,
+}
+
+#[derive(Default)]
+pub struct RenderOptions {
+    pub output: PathBuf,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub simulate: Option<usize>,
+    pub renderer: Option<Backend>,
+    pub compare: Option<PathBuf>,
+}
+
+impl Options {
+    /// Loads the configuration with the overrides applied. The only error is
+    /// a profile that doesn't exist, which is most likely a typo.
+    pub fn load_config(&self) -> Result<Config, String> {
+        let mut config = match &self.config {
+            Some(path) => Config::load_from(path.clone()),
+            None => Config::load(),
+        };
+        // The overrides apply to the profile, so it needs to be switched to
+        // first.
+        if let Some(profile) = &self.profile {
+            if !config.has_profile(profile) {
+                return Err(format!("Unknown profile: {}", profile));
+            }
+            config.switch_profile(profile);
+        }
+        if let Some(splits) = &self.splits {
+            config.override_splits_path(splits.clone());
+        }
+        if let Some(layout) = &self.layout {
+            config.override_layout_path(layout.clone());
+        }
+        if let Some(timing_method) = self.timing_method {
+            config.override_timing_method(timing_method);
+        }
+        if let Some(comparison) = &self.comparison {
+            config.override_comparison(comparison.clone());
+        }
+        Ok(config)
+    }
+}
+
+pub fn parse() -> Result<Command, String> {
+    let mut args = env::args_os().skip(1).peekable();
+
+    let render = args.peek().map_or(false, |arg| arg == "render");
+    if render {
+        args.next();
+    }
+
+    let mut options = Options::default();
+    let mut render_options = RenderOptions::default();
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        let arg_str = arg.to_string_lossy().into_owned();
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {}", arg_str))
+        };
+        match arg_str.as_str() {
+            "--splits" => options.splits = Some(value()?.into()),
+            "--layout" => options.layout = Some(value()?.into()),
+            "--config" => options.config = Some(value()?.into()),
+            "--profile" => options.profile = Some(value()?.to_string_lossy().into_owned()),
+            "--timing-method" => {
+                options.timing_method = Some(parse_timing_method(&value()?.to_string_lossy())?)
+            }
+            "--comparison" => options.comparison = Some(value()?.to_string_lossy().into_owned()),
+            "--output" if render => output = Some(value()?.into()),
+            "--width" if render => render_options.width = Some(parse_number(&arg_str, value()?)?),
+            "--height" if render => render_options.height = Some(parse_number(&arg_str, value()?)?),
+            "--simulate" if render => {
+                render_options.simulate = Some(parse_number(&arg_str, value()?)?)
+            }
+            "--renderer" if render => {
+                render_options.renderer = Some(parse_renderer(&value()?.to_string_lossy())?)
+            }
+            "--compare" if render => render_options.compare = Some(value()?.into()),
+            _ if arg_str.starts_with("--") => return Err(format!("Unknown argument: {}", arg_str)),
+            _ => {
+                let path = PathBuf::from(arg);
+                if is_layout(&path) {
+                    options.layout = Some(path);
+                } else {
+                    options.splits = Some(path);
+                }
+            }
+        }
+    }
+
+    if render {
+        render_options.output = output.ok_or("The --output argument is required")?;
+        Ok(Command::Render(options, render_options))
+    } else {
+        Ok(Command::Launch(options))
+    }
+}
+
+fn parse_timing_method(value: &str) -> Result<TimingMethod, String> {
+    match value
+        .to_ascii_lowercase()
+        .replace(['-', '_', ' '], "")
+        .as_str()
+    {
+        "realtime" => Ok(TimingMethod::RealTime),
+        "gametime" => Ok(TimingMethod::GameTime),
+        _ => Err(format!("Unknown timing method: {}", value)),
+    }
+}
+
+fn parse_renderer(value: &str) -> Result<Backend, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "software" => Ok(Backend::Software),
+        "piet" => Ok(Backend::Piet),
+        _ => Err(format!("Unknown renderer: {}", value)),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: OsString) -> Result<T, String> {
+    value
+        .to_str()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("Invalid number for {}", arg))
+}
+
+/// Determines whether a file is a layout rather than splits. The extension is
+/// used if it is a known one, otherwise the contents are checked.
+fn is_layout(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) if extension.eq_ignore_ascii_case("lss") => return false,
+        Some(extension)
+            if extension.eq_ignore_ascii_case("lsl") || extension.eq_ignore_ascii_case("ls1l") =>
+        {
+            return true
+        }
+        _ => {}
+    }
+
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(_) => return false,
+    };
+    LayoutSettings::from_json(contents.as_slice()).is_ok()
+        || String::from_utf8_lossy(&contents).contains("<Layout")
+}
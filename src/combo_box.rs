@@ -1,3 +1,5 @@
+use std::{ops::Deref, rc::Rc};
+
 use druid::{
     commands::CLOSE_WINDOW,
     kurbo::BezPath,
@@ -55,37 +57,53 @@ impl<T, W: Widget<T>> Widget<T> for ComboBox<W> {
 }
 
 pub fn widget(list: &'static [&'static str]) -> impl Widget<usize> {
+    combo_box(list)
+}
+
+/// A combo box for options that are only known at runtime.
+pub fn owned_widget(list: Rc<[String]>) -> impl Widget<usize> {
+    combo_box(list)
+}
+
+fn combo_box<L, S>(list: L) -> impl Widget<usize>
+where
+    L: Deref<Target = [S]> + Clone + 'static,
+    S: AsRef<str>,
+{
     ComboBox(
-        Button::new(move |&index: &usize, _: &_| list[index].to_owned())
-            .on_click(move |ctx, &mut index: &mut usize, env| {
-                ctx.new_sub_window(
-                    WindowConfig::default()
-                        .show_titlebar(false)
-                        .resizable(false)
-                        .transparent(true)
-                        .window_size(Size::new(
-                            ctx.size().width,
-                            25.0 * list.len().min(8) as f64 + 2.0,
-                        ))
-                        .set_position(ctx.to_screen(Point::new(0.0, ctx.size().height - 1.0)))
-                        .set_level(WindowLevel::DropDown(ctx.window().clone())),
-                    drop_down(list),
-                    index,
-                    env.clone(),
-                );
-            })
-            .env_scope(|env, _| {
-                env.set(theme::BUTTON_BORDER_RADIUS, 0.0);
-                env.set(theme::BUTTON_LIGHT, Color::grey8(0x10));
-                env.set(theme::BUTTON_DARK, Color::grey8(0x10));
-            }),
+        Button::new({
+            let list = list.clone();
+            move |&index: &usize, _: &_| list[index].as_ref().to_owned()
+        })
+        .on_click(move |ctx, &mut index: &mut usize, env| {
+            ctx.new_sub_window(
+                WindowConfig::default()
+                    .show_titlebar(false)
+                    .resizable(false)
+                    .transparent(true)
+                    .window_size(Size::new(
+                        ctx.size().width,
+                        25.0 * list.len().min(8) as f64 + 2.0,
+                    ))
+                    .set_position(ctx.to_screen(Point::new(0.0, ctx.size().height - 1.0)))
+                    .set_level(WindowLevel::DropDown(ctx.window().clone())),
+                drop_down(&list),
+                index,
+                env.clone(),
+            );
+        })
+        .env_scope(|env, _| {
+            env.set(theme::BUTTON_BORDER_RADIUS, 0.0);
+            env.set(theme::BUTTON_LIGHT, Color::grey8(0x10));
+            env.set(theme::BUTTON_DARK, Color::grey8(0x10));
+        }),
     )
 }
 
-fn drop_down(list: &'static [&'static str]) -> impl Widget<usize> {
+fn drop_down(list: &[impl AsRef<str>]) -> impl Widget<usize> {
     let mut flex = Flex::column();
-    for (index, &item) in list.iter().enumerate() {
-        let label = Label::new(item)
+    for (index, item) in list.iter().enumerate() {
+        let label = Label::new(item.as_ref().to_owned())
             .expand_width()
             .center()
             .fix_height(25.0)
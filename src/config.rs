@@ -1,42 +1,206 @@
 use directories::ProjectDirs;
-use druid::WindowDesc;
+use druid::{ExtEventSink, Point, Rect, Screen, Size, WindowDesc};
 use livesplit_core::{
+    auto_splitting,
     layout::{self, Layout, LayoutSettings},
     run::{
-        parser::composite,
-        saver::livesplit::{save_timer, IoWrite},
+        parser::{
+            composite::{self, ParsedRun},
+            TimerKind,
+        },
+        saver::livesplit::{save_run, IoWrite},
     },
-    HotkeyConfig, HotkeySystem, Run, Segment, Timer, TimingMethod,
+    HotkeyConfig, HotkeySystem, Run, Segment, SharedTimer, Timer, TimingMethod,
 };
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
+    fmt,
     fs::{self, create_dir_all, File},
-    io::{BufReader, BufWriter, Read},
+    io::{self, BufReader, BufWriter, ErrorKind},
+    mem,
+    net::SocketAddr,
     path::{Path, PathBuf},
 };
 
-use crate::{timer_form, MainState};
+use crate::{
+    auto_splitter_debug::{self, AutoSplitterLog},
+    auto_splitter_settings, backups, renderer,
+    reset_hotkey::ResetHotkey,
+    server, timer_form, web_socket, MainState,
+};
 
 #[derive(Default, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Config {
     #[serde(default)]
     general: General,
+    /// Overrides from the command line that only apply to this session.
+    #[serde(skip)]
+    overrides: General,
+    /// The file the configuration was loaded from, if it isn't the default.
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// Why the configuration file couldn't be loaded, to be shown once the
+    /// timer's window is open.
+    #[serde(skip)]
+    load_error: Option<String>,
+    /// The configuration file that couldn't be parsed, until it's moved aside.
+    #[serde(skip)]
+    broken_file: Option<PathBuf>,
+    /// Why the files passed on the command line couldn't be opened, titled by
+    /// what was being opened.
+    #[serde(skip)]
+    open_errors: Vec<(&'static str, String)>,
+    /// The timer the current splits are from, if they were opened from a
+    /// file.
+    #[serde(skip)]
+    splits_kind: Option<TimerKind>,
+    #[serde(skip)]
+    auto_splitter_status: AutoSplitterStatus,
     #[serde(default)]
     log: Log,
     #[serde(default)]
     window: Window,
     #[serde(default)]
     hotkeys: HotkeyConfig,
+    #[serde(default)]
+    server: Server,
+    #[serde(default)]
+    web_socket: WebSocket,
+    #[serde(default)]
+    backups: Backups,
+    #[serde(default)]
+    splits_associations: BTreeMap<PathBuf, SplitsAssociation>,
+    #[serde(default)]
+    recent_splits: RecentFiles,
+    #[serde(default)]
+    recent_layouts: RecentFiles,
+    /// The name of the profile the general settings, the window and the
+    /// hotkeys belong to.
+    profile: Option<String>,
+    /// The profiles that aren't currently used.
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// The settings that differ between the people sharing a computer.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Profile {
+    #[serde(default)]
+    general: General,
+    #[serde(default)]
+    window: Window,
+    #[serde(default)]
+    hotkeys: HotkeyConfig,
 }
 
+const DEFAULT_PROFILE: &str = "Default";
+
+#[derive(Clone, Copy)]
+pub enum FileKind {
+    Splits,
+    Layout,
+}
+
+/// The files the user opened recently, most recent first. Pinned files are
+/// kept until they are unpinned.
 #[derive(Default, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct RecentFiles {
+    pinned: Vec<PathBuf>,
+    recent: Vec<PathBuf>,
+}
+
+impl RecentFiles {
+    const MAX_RECENT: usize = 10;
+
+    pub fn pinned(&self) -> &[PathBuf] {
+        &self.pinned
+    }
+
+    pub fn recent(&self) -> &[PathBuf] {
+        &self.recent
+    }
+
+    pub fn is_pinned(&self, path: &Path) -> bool {
+        self.pinned.iter().any(|pinned| pinned == path)
+    }
+
+    fn add(&mut self, path: &Path) {
+        if self.is_pinned(path) {
+            return;
+        }
+        self.recent.retain(|recent| recent != path);
+        self.recent.insert(0, path.to_path_buf());
+        self.recent.truncate(Self::MAX_RECENT);
+    }
+
+    fn toggle_pinned(&mut self, path: &Path) {
+        if self.is_pinned(path) {
+            self.pinned.retain(|pinned| pinned != path);
+            self.add(path);
+        } else {
+            self.recent.retain(|recent| recent != path);
+            self.pinned.push(path.to_path_buf());
+        }
+    }
+}
+
+/// The auto splitter the runtime is running. If one fails to load, the
+/// previous one keeps running.
+#[derive(Default)]
+pub struct AutoSplitterStatus {
+    pub loaded: Option<PathBuf>,
+    /// Why the last auto splitter that was tried couldn't be loaded.
+    pub error: Option<String>,
+}
+
+/// Why a configuration file couldn't be parsed.
+#[derive(Debug)]
+pub enum ParseError {
+    Io(io::Error),
+    /// The file isn't valid. The error knows the line and column where it
+    /// occurred.
+    Yaml(serde_yaml::Error),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io(e) => e.fmt(f),
+            // This already mentions the line and column.
+            ParseError::Yaml(e) => e.fmt(f),
+        }
+    }
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
 struct General {
     splits: Option<PathBuf>,
     layout: Option<PathBuf>,
     timing_method: Option<TimingMethod>,
     comparison: Option<String>,
+    /// Resets that would discard new best times are done without asking.
+    #[serde(default)]
+    skip_reset_confirmation: bool,
+}
+
+/// Settings that belong to a specific splits file rather than the whole
+/// application, keyed by the path of the splits file.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct SplitsAssociation {
+    auto_splitter: Option<PathBuf>,
+    /// The layout last used with the splits.
+    layout: Option<PathBuf>,
+    /// The comparison last used with the splits.
+    comparison: Option<String>,
+    /// The timing method last used with the splits.
+    timing_method: Option<TimingMethod>,
 }
 
 #[derive(Default, Deserialize, Serialize)]
@@ -49,12 +213,96 @@ struct Log {
     clear: bool,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 #[serde(default)]
 struct Window {
     width: f64,
     height: f64,
+    x: Option<f64>,
+    y: Option<f64>,
+    /// The bounds of the monitor the window was on. The position is only
+    /// restored if that monitor is still connected.
+    monitor: Option<MonitorBounds>,
+    always_on_top: bool,
+    /// Limits how often the window is drawn during an attempt. Without a
+    /// limit, it's drawn as often as the display refreshes.
+    max_fps: Option<u32>,
+    renderer: renderer::Backend,
+}
+
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct MonitorBounds {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl From<Rect> for MonitorBounds {
+    fn from(rect: Rect) -> Self {
+        Self {
+            x: rect.x0,
+            y: rect.y0,
+            width: rect.width(),
+            height: rect.height(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+struct Server {
+    enable: bool,
+    address: SocketAddr,
+}
+
+impl Default for Server {
+    fn default() -> Server {
+        Self {
+            enable: false,
+            // The port the LiveSplit Server component uses by default.
+            address: SocketAddr::from(([127, 0, 0, 1], 16834)),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+struct WebSocket {
+    enable: bool,
+    address: SocketAddr,
+}
+
+impl Default for WebSocket {
+    fn default() -> WebSocket {
+        Self {
+            enable: false,
+            address: SocketAddr::from(([127, 0, 0, 1], 16835)),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+struct Backups {
+    enable: bool,
+    /// How many backups are kept of each file. At least the most recent one
+    /// is always kept.
+    retention: usize,
+}
+
+impl Default for Backups {
+    fn default() -> Backups {
+        Self {
+            enable: true,
+            retention: 20,
+        }
+    }
 }
 
 impl Default for Window {
@@ -62,6 +310,12 @@ impl Default for Window {
         Self {
             width: 300.0,
             height: 500.0,
+            x: None,
+            y: None,
+            monitor: None,
+            always_on_top: false,
+            max_fps: None,
+            renderer: renderer::Backend::Software,
         }
     }
 }
@@ -69,14 +323,97 @@ impl Default for Window {
 impl Config {
     pub fn load() -> Self {
         Self::config_path()
-            .and_then(Self::parse)
+            .map(|path| Self::load_or_default(&path))
             .unwrap_or_default()
     }
 
-    pub fn save(&self) -> Option<()> {
-        let path = Self::config_path()?;
-        create_dir_all(path.parent()?).ok()?;
-        self.serialize(path)
+    pub fn load_from(path: PathBuf) -> Self {
+        let mut config = Self::load_or_default(&path);
+        config.path = Some(path);
+        config
+    }
+
+    /// Falls back to the default configuration if the file can't be loaded.
+    fn load_or_default(path: &Path) -> Self {
+        let error = match Self::parse(path) {
+            Ok(config) => return config,
+            Err(ParseError::Io(e)) if e.kind() == ErrorKind::NotFound => return Self::default(),
+            Err(e) => e,
+        };
+
+        Self {
+            load_error: Some(format!(
+                "The configuration file {} couldn't be loaded: {}",
+                path.display(),
+                error
+            )),
+            broken_file: match error {
+                ParseError::Yaml(_) => Some(path.to_path_buf()),
+                ParseError::Io(_) => None,
+            },
+            ..Self::default()
+        }
+    }
+
+    /// Moves a configuration file that couldn't be parsed aside, so saving the
+    /// defaults doesn't overwrite the user's settings. This is only done for
+    /// the timer's window, as rendering never saves the configuration.
+    pub fn move_broken_file_aside(&mut self) {
+        let path = match self.broken_file.take() {
+            Some(path) => path,
+            None => return,
+        };
+        let broken_path = with_added_extension(
+            &path,
+            &chrono::Local::now()
+                .format("%Y-%m-%d_%H-%M-%S.broken")
+                .to_string(),
+        );
+        let note = match fs::rename(&path, &broken_path) {
+            Ok(()) => format!(
+                "\n\nThe default settings are used instead. The broken file was moved to {}.",
+                broken_path.display()
+            ),
+            Err(e) => format!(
+                "\n\nThe default settings are used instead. The broken file couldn't be moved aside: {}",
+                e
+            ),
+        };
+        if let Some(error) = &mut self.load_error {
+            error.push_str(&note);
+        }
+    }
+
+    /// The reason the configuration file couldn't be loaded, if any.
+    pub fn load_error(&self) -> Option<&str> {
+        self.load_error.as_deref()
+    }
+
+    /// Takes the reason the configuration file couldn't be loaded, so it's
+    /// only reported once.
+    pub fn take_load_error(&mut self) -> Option<String> {
+        self.load_error.take()
+    }
+
+    /// Takes why the files passed on the command line couldn't be opened,
+    /// along with the title to report them with.
+    pub fn take_open_errors(&mut self) -> Vec<(&'static str, String)> {
+        mem::take(&mut self.open_errors)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = self
+            .path
+            .clone()
+            .or_else(Self::config_path)
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "No configuration directory"))?;
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        self.serialize(path).map_err(|e| {
+            log::error!("Failed to save the configuration: {}", e);
+            e
+        })
     }
 
     pub fn config_path() -> Option<PathBuf> {
@@ -91,102 +428,626 @@ impl Config {
         )
     }
 
-    pub fn parse(path: impl AsRef<Path>) -> Option<Self> {
-        let buf = BufReader::new(fs::File::open(path).ok()?);
-        serde_yaml::from_reader(buf).ok()
+    pub fn parse(path: impl AsRef<Path>) -> Result<Self, ParseError> {
+        let buf = BufReader::new(fs::File::open(path).map_err(ParseError::Io)?);
+        serde_yaml::from_reader(buf).map_err(ParseError::Yaml)
     }
 
-    pub fn serialize(&self, path: impl AsRef<Path>) -> Option<()> {
-        let file = BufWriter::new(fs::File::open(path).ok()?);
-        serde_yaml::to_writer(file, self).ok()?;
-        Some(())
+    /// Writes the configuration without ever leaving a truncated file behind.
+    /// The previous version is kept as a backup.
+    pub fn serialize(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        write_atomically(path, |file| {
+            serde_yaml::to_writer(file, self).map_err(|e| io::Error::new(ErrorKind::Other, e))?;
+            if path.exists() {
+                fs::copy(path, with_added_extension(path, "bak"))?;
+            }
+            Ok(())
+        })
     }
 
-    pub fn parse_run(&self) -> Option<Run> {
-        let path = self.general.splits.clone()?;
-        let mut file = File::open(&path).ok()?;
-        let mut file_contents = Vec::new();
-        let _size = file.read_to_end(&mut file_contents).ok()?;
-        let mut run = composite::parse(file_contents.as_slice(), Some(path), true)
-            .ok()?
-            .run;
-        run.fix_splits();
-        Some(run)
+    fn parse_splits(&self) -> Option<ParsedRun> {
+        Self::parse_splits_from(self.splits_path()?).ok()
     }
 
-    pub fn parse_run_or_default(&self) -> Run {
-        self.parse_run().unwrap_or_else(|| {
-            let mut run = Run::new();
-            run.push_segment(Segment::new("Time"));
-            run
-        })
+    /// Parses splits in any of the supported formats, along with the timer
+    /// they are from. The error is meant to be shown to the user.
+    pub fn parse_splits_from(path: &Path) -> Result<ParsedRun, String> {
+        let file_contents = fs::read(path).map_err(|e| e.to_string())?;
+        let mut parsed = composite::parse(file_contents.as_slice(), Some(path.to_path_buf()), true)
+            .map_err(|e| e.to_string())?;
+        parsed.run.fix_splits();
+        Ok(parsed)
+    }
+
+    /// Parses splits in any of the supported formats. The error is meant to
+    /// be shown to the user.
+    pub fn parse_run_from(path: &Path) -> Result<Run, String> {
+        Self::parse_splits_from(path).map(|parsed| parsed.run)
+    }
+
+    /// Falls back to the default splits if there are none. Splits passed on
+    /// the command line that can't be opened are reported.
+    pub fn parse_run_or_default(&mut self) -> Run {
+        let parsed = if let Some(path) = &self.overrides.splits {
+            match Self::parse_splits_from(path) {
+                Ok(parsed) if !parsed.run.is_empty() => Some(parsed),
+                result => {
+                    let error = match result {
+                        Ok(_) => String::from("The splits don't contain any segments."),
+                        Err(e) => e,
+                    };
+                    let message = format!(
+                        "The splits {} couldn't be opened: {}",
+                        path.display(),
+                        error
+                    );
+                    self.open_errors.push(("Failed to Open Splits", message));
+                    None
+                }
+            }
+        } else {
+            self.parse_splits().filter(|parsed| !parsed.run.is_empty())
+        };
+
+        match parsed {
+            Some(parsed) => {
+                self.splits_kind = Some(parsed.kind);
+                parsed.run
+            }
+            None => {
+                self.splits_kind = None;
+                Self::default_run()
+            }
+        }
+    }
+
+    /// The splits used when there are no others. They always have a segment.
+    pub fn default_run() -> Run {
+        let mut run = Run::new();
+        run.push_segment(Segment::new("Time"));
+        run
+    }
+
+    /// Remembers which timer the current splits are from, as only splits of
+    /// LiveSplit can be saved back to their file.
+    pub fn set_splits_kind(&mut self, kind: Option<TimerKind>) {
+        self.splits_kind = kind;
+    }
+
+    /// Whether the splits can be saved to the file they were opened from.
+    /// Splits of other timers aren't overwritten, as they are saved in
+    /// LiveSplit's format.
+    pub fn can_save_splits(&self) -> bool {
+        self.splits_path().is_some()
+            && self
+                .splits_kind
+                .as_ref()
+                .map_or(true, |kind| matches!(kind, TimerKind::LiveSplit))
     }
 
     pub fn is_game_time(&self) -> bool {
-        self.general.timing_method == Some(TimingMethod::GameTime)
+        self.overrides
+            .timing_method
+            .or_else(|| self.splits_association()?.timing_method)
+            .or(self.general.timing_method)
+            == Some(TimingMethod::GameTime)
     }
 
     pub fn parse_layout(&self) -> Option<Layout> {
-        // TODO: Use these for open splits in the right click menu.
-        let path = self.general.layout.as_ref()?;
-        let mut file = File::open(path).ok()?;
-        let mut file_contents = String::new();
-        let _size = file.read_to_string(&mut file_contents).ok()?;
+        Self::parse_layout_from(self.layout_path()?).ok()
+    }
+
+    /// Parses a layout of either LiveSplit One or the original LiveSplit. The
+    /// error is meant to be shown to the user.
+    pub fn parse_layout_from(path: &Path) -> Result<Layout, String> {
+        let file_contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
 
-        if let Ok(settings) = LayoutSettings::from_json(file_contents.as_bytes()) {
-            return Some(Layout::from_settings(settings));
+        match LayoutSettings::from_json(file_contents.as_bytes()) {
+            Ok(settings) => Ok(Layout::from_settings(settings)),
+            // Only report why it isn't a valid LiveSplit One layout if it
+            // looks like one. Otherwise the original LiveSplit's format is the
+            // better guess.
+            Err(e) if file_contents.trim_start().starts_with('{') => Err(e.to_string()),
+            Err(_) => layout::parser::parse(file_contents.as_str()).map_err(|e| e.to_string()),
         }
+    }
+
+    /// Whether the layout can be saved to the file it was opened from. Layouts
+    /// of the original LiveSplit aren't overwritten, as they are saved in the
+    /// LiveSplit One format.
+    pub fn can_save_layout(&self) -> bool {
+        self.layout_path().map_or(false, |path| {
+            !path
+                .extension()
+                .map_or(false, |extension| extension.eq_ignore_ascii_case("lsl"))
+        })
+    }
 
-        layout::parser::parse(file_contents.as_str()).ok()
+    /// Saves the layout to the file it was opened from.
+    pub fn save_layout(&self, layout: &Layout) -> io::Result<()> {
+        let path = self
+            .layout_path()
+            .filter(|_| self.can_save_layout())
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::NotFound,
+                    "The layout hasn't been saved to a file yet",
+                )
+            })?;
+        self.save_layout_to(path, layout)
     }
 
-    pub fn parse_layout_or_default(&self) -> Layout {
+    pub fn save_layout_to(&self, path: &Path, layout: &Layout) -> io::Result<()> {
+        self.back_up(path);
+        write_atomically(path, |file| {
+            layout
+                .settings()
+                .write_json(file)
+                .map_err(|e| io::Error::new(ErrorKind::Other, e))
+        })
+    }
+
+    /// Falls back to the default layout if there is none. A layout passed on
+    /// the command line that can't be opened is reported.
+    pub fn parse_layout_or_default(&mut self) -> Layout {
+        if let Some(path) = &self.overrides.layout {
+            match Self::parse_layout_from(path) {
+                Ok(layout) => return layout,
+                Err(e) => {
+                    let message =
+                        format!("The layout {} couldn't be opened: {}", path.display(), e);
+                    self.open_errors.push(("Failed to Open Layout", message));
+                }
+            }
+        }
         self.parse_layout().unwrap_or_else(Layout::default_layout)
     }
 
+    fn splits_path(&self) -> Option<&Path> {
+        self.overrides
+            .splits
+            .as_deref()
+            .or(self.general.splits.as_deref())
+    }
+
+    fn layout_path(&self) -> Option<&Path> {
+        self.overrides
+            .layout
+            .as_deref()
+            .or(self.general.layout.as_deref())
+    }
+
+    /// Uses different splits without saving them to the configuration.
+    pub fn override_splits_path(&mut self, path: PathBuf) {
+        self.overrides.splits = Some(path);
+    }
+
+    /// Uses a different layout without saving it to the configuration.
+    pub fn override_layout_path(&mut self, path: PathBuf) {
+        self.overrides.layout = Some(path);
+    }
+
+    /// Uses a different timing method without saving it to the configuration.
+    pub fn override_timing_method(&mut self, timing_method: TimingMethod) {
+        self.overrides.timing_method = Some(timing_method);
+    }
+
+    /// Uses a different comparison without saving it to the configuration.
+    pub fn override_comparison(&mut self, comparison: String) {
+        self.overrides.comparison = Some(comparison);
+    }
+
+    pub fn window_size(&self) -> (f64, f64) {
+        (self.window.width, self.window.height)
+    }
+
+    /// Remembers the window's size. It's only saved along with the rest of the
+    /// configuration.
+    pub fn set_window_size(&mut self, size: Size) {
+        self.window.width = size.width;
+        self.window.height = size.height;
+    }
+
+    /// Remembers the window's position and the monitor it is on. It's only
+    /// saved along with the rest of the configuration.
+    pub fn set_window_position(&mut self, position: Point) {
+        self.window.x = Some(position.x);
+        self.window.y = Some(position.y);
+        self.window.monitor = Screen::get_monitors()
+            .iter()
+            .map(|monitor| monitor.virtual_rect())
+            .find(|rect| rect.contains(position))
+            .map(MonitorBounds::from);
+    }
+
+    pub fn render_backend(&self) -> renderer::Backend {
+        self.window.renderer
+    }
+
+    pub fn max_fps(&self) -> Option<u32> {
+        self.window.max_fps
+    }
+
+    pub fn always_on_top(&self) -> bool {
+        self.window.always_on_top
+    }
+
+    pub fn set_always_on_top(&mut self, always_on_top: bool) -> io::Result<()> {
+        self.window.always_on_top = always_on_top;
+        self.save()
+    }
+
+    /// The position the window was at last time, unless the monitor it was on
+    /// isn't connected anymore.
+    pub fn window_position(&self) -> Option<Point> {
+        let position = Point::new(self.window.x?, self.window.y?);
+        let monitor = self.window.monitor?;
+        Screen::get_monitors()
+            .iter()
+            .any(|m| MonitorBounds::from(m.virtual_rect()) == monitor)
+            .then_some(position)
+    }
+
     // pub fn set_splits_path(&mut self, path: PathBuf) {
     //     self.general.splits = Some(path);
     // }
 
+    pub fn hotkeys(&self) -> HotkeyConfig {
+        self.hotkeys
+    }
+
     // TODO: Just directly construct the HotkeySystem from the config.
-    pub fn configure_hotkeys(&self, hotkeys: &mut HotkeySystem) {
-        hotkeys.set_config(self.hotkeys).ok();
+    pub fn configure_hotkeys(&self, hotkeys: &mut HotkeySystem, reset_hotkey: &mut ResetHotkey) {
+        let mut config = self.hotkeys;
+        if self.confirm_resets() {
+            // Resets need to go through the timer's window to be confirmed.
+            reset_hotkey.set_key(config.reset.take());
+        } else {
+            reset_hotkey.set_key(None);
+        }
+        hotkeys.set_config(config).ok();
+    }
+
+    pub fn confirm_resets(&self) -> bool {
+        !self.general.skip_reset_confirmation
+    }
+
+    pub fn set_confirm_resets(&mut self, confirm_resets: bool) -> io::Result<()> {
+        self.general.skip_reset_confirmation = !confirm_resets;
+        self.save()
     }
 
     pub fn configure_timer(&self, timer: &mut Timer) {
-        if self.is_game_time() {
-            timer.set_current_timing_method(TimingMethod::GameTime);
-        }
-        if let Some(comparison) = &self.general.comparison {
+        // This is also used when switching profiles, so real time needs to be
+        // set explicitly.
+        timer.set_current_timing_method(if self.is_game_time() {
+            TimingMethod::GameTime
+        } else {
+            TimingMethod::RealTime
+        });
+        if let Some(comparison) = self
+            .overrides
+            .comparison
+            .as_ref()
+            .or_else(|| self.splits_association()?.comparison.as_ref())
+            .or(self.general.comparison.as_ref())
+        {
             timer.set_current_comparison(comparison.as_str()).ok();
         }
     }
 
-    pub fn save_splits(&self, timer: &Timer) {
-        if let Some(path) = &self.general.splits {
-            // FIXME: Don't ignore not being able to save.
-            if let Ok(file) = File::create(path) {
-                save_timer(timer, IoWrite(BufWriter::new(file))).ok();
+    pub fn profile(&self) -> &str {
+        self.profile.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
+
+    pub fn profile_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.profiles.keys().map(String::as_str).collect();
+        names.push(self.profile());
+        names.sort_unstable();
+        names
+    }
+
+    /// Makes the profile the current one, creating it with the default
+    /// settings if it doesn't exist yet. Overrides from the command line don't
+    /// apply to the new profile. Nothing is saved.
+    pub fn switch_profile(&mut self, name: &str) {
+        if name == self.profile() {
+            return;
+        }
+        let previous = Profile {
+            general: mem::take(&mut self.general),
+            window: mem::take(&mut self.window),
+            hotkeys: mem::take(&mut self.hotkeys),
+        };
+        self.profiles.insert(self.profile().to_owned(), previous);
+
+        let next = self.profiles.remove(name).unwrap_or_default();
+        self.general = next.general;
+        self.window = next.window;
+        self.hotkeys = next.hotkeys;
+        self.profile = Some(name.to_owned());
+        self.overrides = General::default();
+    }
+
+    /// Creates a copy of the current profile and switches to it. Nothing is
+    /// saved.
+    pub fn duplicate_profile(&mut self) {
+        let name = (2..)
+            .map(|index| format!("Profile {}", index))
+            .find(|name| name != self.profile() && !self.profiles.contains_key(name))
+            .unwrap();
+        let (general, window, hotkeys) = (self.general.clone(), self.window.clone(), self.hotkeys);
+        self.switch_profile(&name);
+        self.general = general;
+        self.window = window;
+        self.hotkeys = hotkeys;
+    }
+
+    /// Removes a profile that isn't the current one. Nothing is saved.
+    pub fn delete_profile(&mut self, name: &str) {
+        self.profiles.remove(name);
+    }
+
+    pub fn has_profile(&self, name: &str) -> bool {
+        name == self.profile() || self.profiles.contains_key(name)
+    }
+
+    /// Gives the current profile a new name. The error is meant to be shown
+    /// to the user. Nothing is saved.
+    pub fn rename_profile(&mut self, name: &str) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(String::from("The name can't be empty."));
+        }
+        if name != self.profile() && self.has_profile(name) {
+            return Err(format!("There already is a profile called {}.", name));
+        }
+        self.profile = Some(name.to_owned());
+        Ok(())
+    }
+
+    /// Saves the splits to the file they were opened from.
+    pub fn save_splits(&self, run: &Run) -> io::Result<()> {
+        let path = self
+            .splits_path()
+            .filter(|_| self.can_save_splits())
+            .ok_or_else(|| {
+                io::Error::new(
+                    ErrorKind::NotFound,
+                    "The splits haven't been saved to a file yet",
+                )
+            })?;
+        self.save_splits_to(path, run)
+    }
+
+    pub fn save_splits_to(&self, path: &Path, run: &Run) -> io::Result<()> {
+        self.back_up(path);
+        write_atomically(path, |file| {
+            save_run(run, IoWrite(file))
+                .map_err(|_| io::Error::new(ErrorKind::Other, "Failed to write the splits"))
+        })
+    }
+
+    /// Keeps a copy of a file that is about to be overwritten. Not being able
+    /// to do so shouldn't keep the user from saving though.
+    fn back_up(&self, path: &Path) {
+        if self.backups.enable {
+            if let Err(e) = backups::create(path, self.backups.retention) {
+                log::error!("Failed to back up {}: {}", path.display(), e);
             }
         }
     }
 
-    pub fn set_hotkeys(&mut self, hotkeys: HotkeyConfig) {
+    pub fn has_splits_path(&self) -> bool {
+        self.splits_path().is_some()
+    }
+
+    pub fn auto_splitter_path(&self) -> Option<&Path> {
+        let splits = self.splits_path()?;
+        self.splits_associations
+            .get(splits)?
+            .auto_splitter
+            .as_deref()
+    }
+
+    pub fn set_auto_splitter_path(&mut self, path: Option<&Path>) -> io::Result<()> {
+        if let Some(splits) = self.splits_path().map(Path::to_path_buf) {
+            self.splits_associations
+                .entry(splits)
+                .or_default()
+                .auto_splitter = path.map(|path| path.to_path_buf());
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// The auto splitter the runtime is running, which isn't necessarily the
+    /// configured one.
+    pub fn auto_splitter_status(&self) -> &AutoSplitterStatus {
+        &self.auto_splitter_status
+    }
+
+    /// Loads the auto splitter associated with the current splits into the
+    /// runtime, or unloads the current one if there is none.
+    pub fn maybe_load_auto_splitter(
+        &mut self,
+        runtime: &auto_splitting::Runtime,
+        timer: &SharedTimer,
+    ) -> Result<(), String> {
+        if let Some(auto_splitter) = self.auto_splitter_path().map(Path::to_path_buf) {
+            self.load_auto_splitter(&auto_splitter, runtime, timer)
+        } else {
+            // The only error is that there is no script loaded, which is fine.
+            let _ = runtime.unload_script_blocking();
+            self.auto_splitter_status = AutoSplitterStatus::default();
+            Ok(())
+        }
+    }
+
+    /// Loads the auto splitter into the runtime and applies the settings
+    /// stored in the splits. If it fails to load, the previous one keeps
+    /// running.
+    pub fn load_auto_splitter(
+        &mut self,
+        path: &Path,
+        runtime: &auto_splitting::Runtime,
+        timer: &SharedTimer,
+    ) -> Result<(), String> {
+        // The settings are read up front, as the auto splitter may need the
+        // timer while it's being loaded.
+        let settings = auto_splitter_settings::read(timer.read().unwrap().run());
+        if let Err(e) = runtime.load_script_blocking(path.to_path_buf()) {
+            log::error!("Auto Splitter failed to load: {}", &e);
+            let message = format!(
+                "The auto splitter {} couldn't be loaded: {}",
+                path.display(),
+                e,
+            );
+            self.auto_splitter_status.error = Some(message.clone());
+            return Err(message);
+        }
+        auto_splitter_settings::apply(runtime, &settings);
+        self.auto_splitter_status = AutoSplitterStatus {
+            loaded: Some(path.to_path_buf()),
+            error: None,
+        };
+        Ok(())
+    }
+
+    pub fn maybe_start_server(&self, timer: &SharedTimer, sink: ExtEventSink) {
+        if self.server.enable {
+            if let Err(e) = server::start(self.server.address, timer.clone(), sink) {
+                log::error!("Server failed to start: {}", &e);
+            }
+        }
+    }
+
+    pub fn maybe_start_web_socket(&self, clients: &web_socket::Clients, sink: ExtEventSink) {
+        if self.web_socket.enable {
+            if let Err(e) = web_socket::start(self.web_socket.address, clients.clone(), sink) {
+                log::error!("WebSocket server failed to start: {}", &e);
+            }
+        }
+    }
+
+    pub fn set_hotkeys(&mut self, hotkeys: HotkeyConfig) -> io::Result<()> {
         self.hotkeys = hotkeys;
-        self.save();
+        self.save()
     }
 
-    pub fn set_splits_path(&mut self, path: Option<&Path>) {
+    pub fn set_splits_path(&mut self, path: Option<&Path>) -> io::Result<()> {
+        self.overrides.splits = None;
         self.general.splits = path.map(|path| path.to_path_buf());
-        self.save();
+        if let Some(path) = path {
+            self.recent_splits.add(path);
+        }
+        self.save()
     }
 
-    pub fn set_layout_path(&mut self, path: Option<&Path>) {
+    pub fn set_layout_path(&mut self, path: Option<&Path>) -> io::Result<()> {
+        self.overrides.layout = None;
         self.general.layout = path.map(|path| path.to_path_buf());
-        self.save();
+        if let Some(path) = path {
+            self.recent_layouts.add(path);
+        }
+        if let Some(splits) = self.splits_path().map(Path::to_path_buf) {
+            self.splits_associations.entry(splits).or_default().layout =
+                path.map(|path| path.to_path_buf());
+        }
+        self.save()
     }
 
-    pub fn setup_logging(&self) -> Option<()> {
+    fn splits_association(&self) -> Option<&SplitsAssociation> {
+        self.splits_associations.get(self.splits_path()?)
+    }
+
+    /// The layout last used with the current splits.
+    pub fn associated_layout(&self) -> Option<&Path> {
+        self.splits_association()?.layout.as_deref()
+    }
+
+    /// Remembers the comparison and the timing method used with the current
+    /// splits, so they are used again the next time the splits are opened.
+    /// Whatever is overridden from the command line only applies to this
+    /// session, so it isn't remembered. Nothing is saved.
+    pub fn remember_timer_settings(&mut self, timer: &Timer) {
+        if let Some(splits) = self.splits_path().map(Path::to_path_buf) {
+            let association = self.splits_associations.entry(splits).or_default();
+            if self.overrides.comparison.is_none() {
+                association.comparison = Some(timer.current_comparison().to_owned());
+            }
+            if self.overrides.timing_method.is_none() {
+                association.timing_method = Some(timer.current_timing_method());
+            }
+        }
+    }
+
+    pub fn recent_files(&self, kind: FileKind) -> &RecentFiles {
+        match kind {
+            FileKind::Splits => &self.recent_splits,
+            FileKind::Layout => &self.recent_layouts,
+        }
+    }
+
+    fn recent_files_mut(&mut self, kind: FileKind) -> &mut RecentFiles {
+        match kind {
+            FileKind::Splits => &mut self.recent_splits,
+            FileKind::Layout => &mut self.recent_layouts,
+        }
+    }
+
+    /// The file of that kind that is currently open.
+    pub fn current_file(&self, kind: FileKind) -> Option<&Path> {
+        match kind {
+            FileKind::Splits => self.splits_path(),
+            FileKind::Layout => self.layout_path(),
+        }
+    }
+
+    /// Pins the file, or unpins it if it already is.
+    pub fn toggle_pinned(&mut self, kind: FileKind, path: &Path) -> io::Result<()> {
+        self.recent_files_mut(kind).toggle_pinned(path);
+        self.save()
+    }
+
+    /// Forgets the recent files of that kind. Pinned files are kept.
+    pub fn clear_recent(&mut self, kind: FileKind) -> io::Result<()> {
+        self.recent_files_mut(kind).recent.clear();
+        self.save()
+    }
+
+    pub fn setup_logging(&self, auto_splitter_log: &AutoSplitterLog) -> Option<()> {
+        let auto_splitter_log = auto_splitter_log.clone();
+        // Only the auto splitter's messages are let through unless logging to
+        // a file is enabled, so nothing else is formatted for no reason.
+        let level = if self.log.enable {
+            self.log.level.unwrap_or(log::LevelFilter::Warn)
+        } else {
+            log::LevelFilter::Off
+        };
+        let mut dispatch = fern::Dispatch::new()
+            .level(level)
+            .level_for(auto_splitter_debug::LOG_TARGET, log::LevelFilter::Trace)
+            .format(|out, message, record| {
+                out.finish(format_args!(
+                    "{}[{}][{}] {}",
+                    chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
+                    record.target(),
+                    record.level(),
+                    message
+                ))
+            })
+            // The auto splitter's messages are always collected for the debug
+            // window, regardless of whether logging to a file is enabled.
+            .chain(
+                fern::Dispatch::new()
+                    .filter(|metadata| metadata.target() == auto_splitter_debug::LOG_TARGET)
+                    .chain(fern::Output::call(move |record| {
+                        auto_splitter_log.push_message(record.args().to_string())
+                    })),
+            );
+
         if self.log.enable {
             let path = Self::path("log.txt")?;
             create_dir_all(path.parent()?).ok()?;
@@ -199,21 +1060,12 @@ impl Config {
                 .open(&path)
                 .ok()?;
 
-            fern::Dispatch::new()
-                .format(|out, message, record| {
-                    out.finish(format_args!(
-                        "{}[{}][{}] {}",
-                        chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
-                        record.target(),
-                        record.level(),
-                        message
-                    ))
-                })
-                .level(self.log.level.unwrap_or(log::LevelFilter::Warn))
-                .chain(log_file)
-                .apply()
-                .ok()?;
+            dispatch = dispatch.chain(log_file);
+        }
 
+        dispatch.apply().ok()?;
+
+        if self.log.enable {
             #[cfg(not(debug_assertions))]
             {
                 std::panic::set_hook(Box::new(|panic_info| {
@@ -225,13 +1077,38 @@ impl Config {
     }
 
     pub fn build_window(&self) -> WindowDesc<MainState> {
-        WindowDesc::new(timer_form::root_widget())
+        let window = WindowDesc::new(timer_form::root_widget())
             .title("LiveSplit One")
             .with_min_size((50.0, 50.0))
             .window_size((self.window.width, self.window.height))
             .show_titlebar(false)
-            .transparent(true)
-            // .topmost(true) TODO figure out a better best effort way to do this
-            // some windowing libraries do not allow Allways on Top
+            // Always on top is applied once the window is connected, as not
+            // every windowing backend supports it.
+            .transparent(true);
+
+        match self.window_position() {
+            Some(position) => window.set_position(position),
+            None => window,
+        }
     }
 }
+
+/// Writes to a temporary file first and then moves it into place, so a failed
+/// write never leaves a truncated file behind.
+pub(crate) fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let temp_path = with_added_extension(path, "tmp");
+    let mut file = BufWriter::new(File::create(&temp_path)?);
+    write(&mut file)?;
+    file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    fs::rename(&temp_path, path)
+}
+
+fn with_added_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    path.into()
+}
@@ -0,0 +1,154 @@
+use druid::{
+    commands,
+    widget::{
+        Button, CrossAxisAlignment, Flex, Label, LineBreaking, Scope, ScopeTransfer, TextBox,
+    },
+    Command, Selector, Target, Widget, WidgetExt, WindowDesc,
+};
+
+use crate::{
+    consts::{BUTTON_SPACING, DIALOG_BUTTON_HEIGHT, DIALOG_BUTTON_WIDTH, MARGIN},
+    MainState,
+};
+
+/// Reports an error to the user in a dialog. Any window can submit this, and so
+/// can other threads through an `ExtEventSink`.
+pub const SHOW_ERROR: Selector<ErrorReport> = Selector::new("show-error");
+
+pub struct ErrorReport {
+    pub title: String,
+    pub message: String,
+}
+
+pub fn show_error(title: impl Into<String>, message: impl Into<String>) -> Command {
+    SHOW_ERROR
+        .with(ErrorReport {
+            title: title.into(),
+            message: message.into(),
+        })
+        .to(Target::Global)
+}
+
+/// A button of a dialog and the command it sends to the timer when clicked.
+/// Every button closes the dialog.
+pub struct DialogButton {
+    label: &'static str,
+    command: Option<Command>,
+}
+
+impl DialogButton {
+    pub fn new(label: &'static str, command: impl Into<Command>) -> Self {
+        Self {
+            label,
+            command: Some(command.into()),
+        }
+    }
+
+    pub fn close(label: &'static str) -> Self {
+        Self {
+            label,
+            command: None,
+        }
+    }
+}
+
+pub fn window(
+    title: &str,
+    message: impl Into<String>,
+    buttons: Vec<DialogButton>,
+) -> WindowDesc<MainState> {
+    WindowDesc::new(root_widget(message.into(), buttons))
+        .title(title)
+        .with_min_size((350.0, 150.0))
+        .window_size((400.0, 180.0))
+        .resizable(false)
+}
+
+pub fn error(title: &str, message: impl Into<String>) -> WindowDesc<MainState> {
+    window(title, message, vec![DialogButton::close("OK")])
+}
+
+/// Asks the user for a line of text, which is sent to the timer with the
+/// command once confirmed.
+pub fn text_input(
+    title: &str,
+    message: impl Into<String>,
+    text: String,
+    command: Selector<String>,
+) -> WindowDesc<MainState> {
+    let buttons = Flex::row()
+        .with_flex_spacer(1.0)
+        .with_child(
+            Button::new("OK")
+                .on_click(move |ctx, text: &mut String, _| {
+                    ctx.submit_command(command.with(text.clone()).to(Target::Global));
+                    ctx.submit_command(commands::CLOSE_WINDOW);
+                })
+                .fix_size(DIALOG_BUTTON_WIDTH, DIALOG_BUTTON_HEIGHT),
+        )
+        .with_spacer(BUTTON_SPACING)
+        .with_child(
+            Button::new("Cancel")
+                .on_click(|ctx, _, _| ctx.submit_command(commands::CLOSE_WINDOW))
+                .fix_size(DIALOG_BUTTON_WIDTH, DIALOG_BUTTON_HEIGHT),
+        );
+    let widget = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(message.into()).with_line_break_mode(LineBreaking::WordWrap))
+        .with_spacer(BUTTON_SPACING)
+        .with_child(TextBox::new().expand_width())
+        .with_flex_spacer(1.0)
+        .with_child(buttons)
+        .padding(MARGIN);
+
+    WindowDesc::new(Scope::from_function(move |_| text, LocalText, widget))
+        .title(title)
+        .with_min_size((350.0, 150.0))
+        .window_size((400.0, 180.0))
+        .resizable(false)
+}
+
+/// The text being entered only belongs to the dialog, so nothing is
+/// transferred to or from the timer's state.
+struct LocalText;
+
+impl ScopeTransfer for LocalText {
+    type In = MainState;
+    type State = String;
+
+    fn read_input(&self, _: &mut String, _: &MainState) {}
+
+    fn write_back_input(&self, _: &String, _: &mut MainState) {}
+}
+
+fn root_widget(message: String, buttons: Vec<DialogButton>) -> impl Widget<MainState> {
+    let mut row = Flex::row().with_flex_spacer(1.0);
+    for (index, DialogButton { label, command }) in buttons.into_iter().enumerate() {
+        if index != 0 {
+            row.add_spacer(BUTTON_SPACING);
+        }
+        row.add_child(
+            Button::new(label)
+                .on_click(move |ctx, _, _| {
+                    if let Some(command) = &command {
+                        // The dialog is its own window, so the command needs to
+                        // reach the timer's window.
+                        ctx.submit_command(command.clone().to(Target::Global));
+                    }
+                    ctx.submit_command(commands::CLOSE_WINDOW);
+                })
+                .fix_size(DIALOG_BUTTON_WIDTH, DIALOG_BUTTON_HEIGHT),
+        );
+    }
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_flex_child(
+            Label::new(message)
+                .with_line_break_mode(LineBreaking::WordWrap)
+                .expand_width(),
+            1.0,
+        )
+        .with_child(row)
+        .padding(MARGIN)
+}
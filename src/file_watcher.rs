@@ -0,0 +1,62 @@
+//! Notices when the open splits or layout are changed by another program, for
+//! example when they are synchronized from another computer. The files are
+//! polled, as they are only checked every few seconds anyway.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::config::FileKind;
+
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+pub struct FileWatcher {
+    splits: WatchedFile,
+    layout: WatchedFile,
+}
+
+#[derive(Default)]
+struct WatchedFile {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+}
+
+impl FileWatcher {
+    /// Takes the file as it is now as known, for example after saving it.
+    pub fn acknowledge(&mut self, kind: FileKind, path: Option<&Path>) {
+        let file = self.file_mut(kind);
+        file.path = path.map(Path::to_path_buf);
+        file.modified = path.and_then(modified);
+    }
+
+    /// Whether the file changed since it was opened, saved or last reported
+    /// as changed. A different file than last time is taken as it is.
+    pub fn has_changed(&mut self, kind: FileKind, path: Option<&Path>) -> bool {
+        let file = self.file_mut(kind);
+        if file.path.as_deref() != path {
+            self.acknowledge(kind, path);
+            return false;
+        }
+        let modified = path.and_then(modified);
+        if modified.is_some() && modified != file.modified {
+            file.modified = modified;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn file_mut(&mut self, kind: FileKind) -> &mut WatchedFile {
+        match kind {
+            FileKind::Splits => &mut self.splits,
+            FileKind::Layout => &mut self.layout,
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).ok()?.modified().ok()
+}
@@ -0,0 +1,162 @@
+//! Renders a layout to an image without opening a window.
+
+use livesplit_core::{layout::LayoutState, rendering::software::Renderer, Timer, TimingMethod};
+
+use crate::{
+    cli::{Options, RenderOptions},
+    config::Config,
+    renderer::Backend,
+};
+
+pub fn render(options: Options, render_options: RenderOptions) -> Result<(), String> {
+    let mut config = options.load_config()?;
+    if let Some(error) = config.load_error() {
+        eprintln!("Warning: {}", error);
+    }
+    // Explicitly requested files need to load, so the image isn't quietly
+    // rendered with the defaults instead.
+    let run = match &options.splits {
+        Some(path) => {
+            Config::parse_run_from(path).map_err(|e| format!("Failed to load the splits: {}", e))?
+        }
+        None => config.parse_run_or_default(),
+    };
+    let mut layout = match &options.layout {
+        Some(path) => Config::parse_layout_from(path)
+            .map_err(|e| format!("Failed to load the layout: {}", e))?,
+        None => config.parse_layout_or_default(),
+    };
+
+    let mut timer =
+        Timer::new(run).map_err(|_| String::from("The splits don't contain any segments"))?;
+    config.configure_timer(&mut timer);
+    if let Some(splits) = render_options.simulate {
+        simulate_attempt(&mut timer, splits);
+    }
+
+    let mut layout_state = LayoutState::default();
+    layout.update_state(&mut layout_state, &timer.snapshot());
+
+    let (window_width, window_height) = config.window_size();
+    let mut dimensions = [
+        render_options.width.unwrap_or(window_width as u32),
+        render_options.height.unwrap_or(window_height as u32),
+    ];
+
+    let backend = render_options
+        .renderer
+        .unwrap_or_else(|| config.render_backend());
+    let (mut image, new_dims) = render_with(backend, &layout_state, dimensions)?;
+    if let Some((width, height)) = new_dims {
+        // The layout prefers a different size, just like the window would be
+        // resized to it. Explicitly requested dimensions are kept though.
+        dimensions = [
+            render_options.width.unwrap_or(width as u32),
+            render_options.height.unwrap_or(height as u32),
+        ];
+        image = render_with(backend, &layout_state, dimensions)?.0;
+    }
+    write_png(&render_options.output, &image, dimensions)?;
+
+    if let Some(compare) = &render_options.compare {
+        let other = match backend {
+            Backend::Software => Backend::Piet,
+            Backend::Piet => Backend::Software,
+        };
+        let (other_image, _) = render_with(other, &layout_state, dimensions)?;
+        write_png(compare, &other_image, dimensions)?;
+        let (mean, max) = difference(&image, &other_image);
+        println!(
+            "The {} and the {} renderer differ by {:.3} on average and by up to {} per channel.",
+            backend.name(),
+            other.name(),
+            mean,
+            max,
+        );
+    }
+    Ok(())
+}
+
+/// Renders the layout with the backend. Returns the premultiplied RGBA pixels
+/// and the size the layout would like to have instead, if any.
+fn render_with(
+    backend: Backend,
+    state: &LayoutState,
+    dimensions: [u32; 2],
+) -> Result<(Vec<u8>, Option<(f32, f32)>), String> {
+    match backend {
+        Backend::Software => {
+            let mut renderer = Renderer::default();
+            let new_dims = renderer.render(state, dimensions);
+            Ok((renderer.image_data().to_vec(), new_dims))
+        }
+        #[cfg(feature = "piet-rendering")]
+        Backend::Piet => crate::piet_renderer::render_image(state, dimensions),
+        #[cfg(not(feature = "piet-rendering"))]
+        Backend::Piet => Err(String::from(
+            "The piet renderer isn't included in this build",
+        )),
+    }
+}
+
+/// The mean and the largest difference between the channels of two images of
+/// the same size.
+fn difference(a: &[u8], b: &[u8]) -> (f64, u8) {
+    let mut sum = 0u64;
+    let mut max = 0;
+    for (a, b) in a.iter().zip(b) {
+        let difference = a.abs_diff(*b);
+        sum += difference as u64;
+        max = max.max(difference);
+    }
+    (sum as f64 / a.len().max(1) as f64, max)
+}
+
+/// Simulates an attempt that completed the given number of splits. Real time
+/// can't be simulated, so the personal best's split times are used as game
+/// time and the timer is switched to game time.
+fn simulate_attempt(timer: &mut Timer, splits: usize) {
+    timer.set_current_timing_method(TimingMethod::GameTime);
+    timer.start();
+    timer.initialize_game_time();
+    timer.pause_game_time();
+
+    let split_times: Vec<_> = timer
+        .run()
+        .segments()
+        .iter()
+        .take(splits)
+        .map(|segment| {
+            let pb = segment.personal_best_split_time();
+            pb.game_time.or(pb.real_time)
+        })
+        .collect();
+
+    for split_time in split_times {
+        if let Some(split_time) = split_time {
+            timer.set_game_time(split_time);
+            timer.split();
+        } else {
+            timer.skip_split();
+        }
+    }
+}
+
+fn write_png(path: &std::path::Path, data: &[u8], [width, height]: [u32; 2]) -> Result<(), String> {
+    // The renderer produces premultiplied alpha, but PNG stores it straight.
+    let mut data = data.to_vec();
+    for pixel in data.chunks_exact_mut(4) {
+        let alpha = pixel[3];
+        if alpha != 0 && alpha != 255 {
+            for channel in &mut pixel[..3] {
+                *channel = (*channel as u32 * 255 / alpha as u32).min(255) as u8;
+            }
+        }
+    }
+
+    let image = image::RgbaImage::from_raw(width, height, data)
+        .ok_or_else(|| String::from("The rendered image has unexpected dimensions"))?;
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to write the image: {}", e))
+}
@@ -32,7 +32,7 @@ use crate::{
         GRID_BORDER, ICON_SIZE, MARGIN, SPACING, TABLE_HORIZONTAL_MARGIN,
     },
     formatter_scope::formatted,
-    settings_table::{self, SettingsRow},
+    settings_table::{self, RowKind, SettingsRow},
     MainState,
 };
 
@@ -76,6 +76,7 @@ impl ListIter<SettingsRow> for State {
             index: 0,
             text: String::new(),
             value: Value::Bool(false),
+            kind: RowKind::Value,
         };
 
         for (index, field) in settings.fields.iter().enumerate() {
@@ -97,6 +98,7 @@ impl ListIter<SettingsRow> for State {
             index: 0,
             text: String::new(),
             value: Value::Bool(false),
+            kind: RowKind::Value,
         };
 
         let mut editor = self.editor.borrow_mut();
@@ -1,30 +1,58 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    process,
+    rc::Rc,
+};
 
-use druid::{kurbo::PathEl, Data, Lens, WindowId};
+use druid::{Data, Lens, WindowId};
 use livesplit_core::{
-    layout::LayoutState, rendering::SceneManager, HotkeySystem, Layout, SharedTimer, Timer,
+    auto_splitting, layout::LayoutState, HotkeySystem, Layout, SharedTimer, Timer,
 };
-// use piet_renderer::{Image, PietResourceAllocator};
 
-use crate::config::Config;
+use crate::{
+    attempt_recovery::RestoredOffset,
+    auto_splitter_debug::AutoSplitterLog,
+    config::{Config, FileKind},
+    file_watcher::FileWatcher,
+    reset_hotkey::ResetHotkey,
+};
 
+mod attempt_recovery;
+mod auto_splitter_debug;
+mod auto_splitter_settings;
+mod backups;
+mod cli;
 mod color_button;
 mod combo_box;
 mod config;
 mod consts;
+mod dialog;
+mod file_watcher;
 mod formatter_scope;
+mod headless;
 mod hotkey_button;
 mod layout_editor;
 mod map_scope;
+mod redraw;
+mod renderer;
+mod reset_hotkey;
 mod run_editor;
+mod server;
 mod settings_editor;
 mod settings_table;
 mod timer_form;
+mod web_socket;
 
+#[cfg(feature = "piet-rendering")]
+mod piet_renderer;
 mod software_renderer;
-// mod piet_renderer;
+
+#[cfg(not(feature = "software-rendering"))]
+compile_error!(
+    "The software-rendering feature is required, as every other renderer falls back to it."
+);
 
 #[derive(Clone, Data, Lens)]
 pub struct MainState {
@@ -35,16 +63,33 @@ pub struct MainState {
     #[data(ignore)]
     hotkey_system: Rc<RefCell<HotkeySystem>>,
     #[data(ignore)]
+    reset_hotkey: Rc<RefCell<ResetHotkey>>,
+    #[data(ignore)]
+    auto_splitter: Rc<auto_splitting::Runtime>,
+    #[data(ignore)]
+    auto_splitter_log: AutoSplitterLog,
+    #[data(ignore)]
+    web_socket: web_socket::Clients,
+    #[data(ignore)]
+    file_watcher: Rc<RefCell<FileWatcher>>,
+    #[data(ignore)]
     config: Rc<RefCell<Config>>,
+    /// Set while a recovered attempt is continued, as its offset must not be
+    /// saved with the splits.
+    #[data(ignore)]
+    restored_offset: Rc<Cell<Option<RestoredOffset>>>,
     run_editor: Option<OpenWindow<run_editor::State>>,
     layout_editor: Option<OpenWindow<layout_editor::State>>,
     settings_editor: Option<OpenWindow<settings_editor::State>>,
+    auto_splitter_settings_editor: Option<OpenWindow<auto_splitter_settings::State>>,
+    auto_splitter_debug: Option<OpenWindow<auto_splitter_debug::State>>,
 }
 
 struct LayoutData {
     layout: Layout,
+    /// Whether the layout was changed since it was opened or saved.
+    modified: bool,
     layout_state: LayoutState,
-    // scene_manager: SceneManager<Rc<[PathEl]>, Rc<Image>>,
 }
 
 #[derive(Clone)]
@@ -60,8 +105,13 @@ impl<T: Data> Data for OpenWindow<T> {
 }
 
 impl MainState {
-    fn new(config: Config) -> Self {
-        config.setup_logging();
+    fn new(mut config: Config) -> Self {
+        config.move_broken_file_aside();
+        let auto_splitter_log = AutoSplitterLog::default();
+        config.setup_logging(&auto_splitter_log);
+        if let Some(error) = config.load_error() {
+            log::error!("{}", error);
+        }
 
         let run = config.parse_run_or_default();
         let mut timer = Timer::new(run).unwrap();
@@ -69,20 +119,36 @@ impl MainState {
 
         let timer = timer.into_shared();
         let mut hotkey_system = HotkeySystem::new(timer.clone()).unwrap();
-        config.configure_hotkeys(&mut hotkey_system);
+        let mut reset_hotkey = ResetHotkey::default();
+        config.configure_hotkeys(&mut hotkey_system, &mut reset_hotkey);
+
+        let auto_splitter = auto_splitting::Runtime::new(timer.clone());
+
+        let mut file_watcher = FileWatcher::default();
+        for kind in [FileKind::Splits, FileKind::Layout] {
+            file_watcher.acknowledge(kind, config.current_file(kind));
+        }
 
         Self {
             timer,
             hotkey_system: Rc::new(RefCell::new(hotkey_system)),
+            reset_hotkey: Rc::new(RefCell::new(reset_hotkey)),
+            auto_splitter: Rc::new(auto_splitter),
+            auto_splitter_log,
+            web_socket: Default::default(),
             layout_data: Rc::new(RefCell::new(LayoutData {
                 layout: config.parse_layout_or_default(),
+                modified: false,
                 layout_state: LayoutState::default(),
-                // scene_manager: SceneManager::new(PietResourceAllocator),
             })),
+            file_watcher: Rc::new(RefCell::new(file_watcher)),
             config: Rc::new(RefCell::new(config)),
+            restored_offset: Rc::new(Cell::new(None)),
             run_editor: None,
             layout_editor: None,
             settings_editor: None,
+            auto_splitter_settings_editor: None,
+            auto_splitter_debug: None,
         }
     }
 }
@@ -131,8 +197,59 @@ impl Lens<MainState, settings_editor::State> for SettingsEditorLens {
     }
 }
 
+struct AutoSplitterSettingsEditorLens;
+
+impl Lens<MainState, auto_splitter_settings::State> for AutoSplitterSettingsEditorLens {
+    fn with<V, F: FnOnce(&auto_splitter_settings::State) -> V>(&self, data: &MainState, f: F) -> V {
+        f(&data.auto_splitter_settings_editor.as_ref().unwrap().state)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut auto_splitter_settings::State) -> V>(
+        &self,
+        data: &mut MainState,
+        f: F,
+    ) -> V {
+        f(&mut data.auto_splitter_settings_editor.as_mut().unwrap().state)
+    }
+}
+
+struct AutoSplitterDebugLens;
+
+impl Lens<MainState, auto_splitter_debug::State> for AutoSplitterDebugLens {
+    fn with<V, F: FnOnce(&auto_splitter_debug::State) -> V>(&self, data: &MainState, f: F) -> V {
+        f(&data.auto_splitter_debug.as_ref().unwrap().state)
+    }
+
+    fn with_mut<V, F: FnOnce(&mut auto_splitter_debug::State) -> V>(
+        &self,
+        data: &mut MainState,
+        f: F,
+    ) -> V {
+        f(&mut data.auto_splitter_debug.as_mut().unwrap().state)
+    }
+}
+
 fn main() {
-    let config = Config::load();
-    let window = config.build_window();
-    timer_form::launch(MainState::new(config), window);
+    match cli::parse() {
+        Ok(cli::Command::Launch(options)) => match options.load_config() {
+            Ok(config) => {
+                let window = config.build_window();
+                timer_form::launch(MainState::new(config), window);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        },
+        Ok(cli::Command::Render(options, render_options)) => {
+            if let Err(e) = headless::render(options, render_options) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    }
 }
@@ -2,13 +2,18 @@ use std::{cell::RefCell, rc::Rc};
 
 use druid::{
     kurbo::PathEl,
-    piet::{Device, ImageFormat, InterpolationMode, PaintBrush, PietImage},
-    Affine, Color, ImageBuf, LinearGradient, PaintCtx, Rect, RenderContext, UnitPoint,
+    piet::{self, Device, ImageFormat, InterpolationMode, PaintBrush, Piet, PietImage},
+    Affine, Color, ImageBuf, LinearGradient, PaintCtx, Rect, RenderContext, Scale, Size, UnitPoint,
 };
-use livesplit_core::rendering::{
-    Entity, FillShader, PathBuilder, ResourceAllocator, Rgba, Scene, Transform,
+use livesplit_core::{
+    layout::LayoutState,
+    rendering::{
+        Entity, FillShader, PathBuilder, ResourceAllocator, Rgba, Scene, SceneManager, Transform,
+    },
 };
 
+use crate::renderer;
+
 pub struct PietResourceAllocator;
 
 pub struct PietPathBuilder(Vec<PathEl>);
@@ -100,38 +105,6 @@ impl ResourceAllocator for PietResourceAllocator {
     }
 }
 
-// pub fn render_scene(ctx: &mut PaintCtx, scene: &Scene<Rc<[PathEl]>, ()>) {
-//     if let Some(background) = scene.background() {
-//         // TODO: We could use .clear(...) maybe
-//         let rect = ctx.size().to_rect();
-//         ctx.fill(rect, &convert_shader(background));
-//     }
-
-//     scene
-//         .bottom_layer()
-//         .iter()
-//         .chain(scene.top_layer())
-//         .for_each(|element| match element {
-//             Entity::FillPath(path, shader, transform) => {
-//                 ctx.with_save(|ctx| {
-//                     ctx.transform(convert_transform(transform));
-//                     ctx.fill(&***path, &convert_shader(shader));
-//                 });
-//             }
-//             Entity::StrokePath(path, stroke_width, color, transform) => {
-//                 ctx.with_save(|ctx| {
-//                     ctx.transform(convert_transform(transform));
-//                     ctx.stroke(
-//                         &***path,
-//                         &PaintBrush::Color(convert_color(color)),
-//                         *stroke_width as f64,
-//                     );
-//                 });
-//             }
-//             Entity::Image(image, transform) => {}
-//         });
-// }
-
 fn render_layer(
     ctx: &mut impl RenderContext<Image = PietImage>,
     layer: &[Entity<Rc<[PathEl]>, Rc<Image>>],
@@ -175,47 +148,141 @@ fn render_layer(
     }
 }
 
-pub fn render_scene(
-    paint_ctx: &mut PaintCtx,
-    bottom_image: &mut PietImage,
-    device: &mut Device,
-    scene: &Scene<Rc<[PathEl]>, Rc<Image>>,
-) {
-    let size = paint_ctx.size();
-    let (width, height) = (size.width, size.height);
+/// Draws the layout as vector graphics, so it stays crisp at any scale. The
+/// bottom layer rarely changes, so it's drawn into an image that is reused
+/// until it does.
+pub struct PietRenderer {
+    scene_manager: SceneManager<Rc<[PathEl]>, Rc<Image>>,
+    device: Device,
+    bottom_image: Option<PietImage>,
+    /// The size of the bottom layer's image in physical pixels.
+    bottom_size: Size,
+}
 
-    // TODO: Bring this back. Potentially requires duplicating images across top
-    // and bottom layer.
+impl PietRenderer {
+    pub fn new() -> Result<Self, piet::Error> {
+        Ok(Self {
+            scene_manager: SceneManager::new(PietResourceAllocator),
+            device: Device::new()?,
+            bottom_image: None,
+            bottom_size: Size::ZERO,
+        })
+    }
 
-    if scene.bottom_layer_changed() {
-        let mut target = device
-            .bitmap_target(width.ceil() as _, height.ceil() as _, 1.0)
-            .unwrap();
-        {
-            let mut ctx = target.render_context();
+    pub fn render_scene(
+        &mut self,
+        paint_ctx: &mut PaintCtx,
+        state: &LayoutState,
+    ) -> Option<(f32, f32)> {
+        let size = paint_ctx.size();
+        let scale = paint_ctx.window().get_scale().unwrap_or_default();
+        self.render(paint_ctx.render_ctx, size, scale, state)
+    }
 
-            // TODO: We shouldn't clear and then fill
-            ctx.clear(None, Color::TRANSPARENT);
-            if let Some(background) = scene.background() {
-                let rect = Rect::new(0.0, 0.0, width, height);
-                ctx.fill(rect, &convert_shader(background));
-            }
+    /// Draws the layout at the size in logical units. The bottom layer's image
+    /// is rendered at the physical resolution, so it's as sharp as the vector
+    /// graphics drawn on top of it.
+    fn render(
+        &mut self,
+        ctx: &mut Piet,
+        size: Size,
+        scale: Scale,
+        state: &LayoutState,
+    ) -> Option<(f32, f32)> {
+        if size.width < 1.0 || size.height < 1.0 {
+            return None;
+        }
+
+        let new_dims = self.scene_manager.update_scene(
+            PietResourceAllocator,
+            (size.width as f32, size.height as f32),
+            state,
+        );
+        let scene = self.scene_manager.scene();
 
-            render_layer(&mut ctx, scene.bottom_layer(), false);
+        let bottom_size = renderer::physical_size(size, scale);
+        if scene.bottom_layer_changed()
+            || self.bottom_image.is_none()
+            || self.bottom_size != bottom_size
+        {
+            self.bottom_image =
+                match render_bottom_layer(&mut self.device, scene, size, bottom_size) {
+                    Ok(image) => Some(image.to_image(ctx)),
+                    Err(e) => {
+                        log::error!("Failed to render the bottom layer of the layout: {}", e);
+                        None
+                    }
+                };
+            self.bottom_size = bottom_size;
+        }
 
-            ctx.finish();
+        if let Some(bottom_image) = &self.bottom_image {
+            ctx.draw_image(
+                bottom_image,
+                size.to_rect(),
+                InterpolationMode::NearestNeighbor,
+            );
         }
-        *bottom_image = target
-            .to_image_buf(ImageFormat::RgbaPremul)
-            .unwrap()
-            .to_image(paint_ctx.render_ctx);
+        render_layer(ctx, scene.top_layer(), true);
+
+        new_dims
     }
+}
 
-    paint_ctx.draw_image(
-        bottom_image,
-        Rect::new(0.0, 0.0, width, height),
-        InterpolationMode::NearestNeighbor,
-    );
+fn render_bottom_layer(
+    device: &mut Device,
+    scene: &Scene<Rc<[PathEl]>, Rc<Image>>,
+    size: Size,
+    pixels: Size,
+) -> Result<ImageBuf, piet::Error> {
+    let mut target = device.bitmap_target(pixels.width as _, pixels.height as _, 1.0)?;
+    {
+        let mut ctx = target.render_context();
 
-    render_layer(paint_ctx.render_ctx, scene.top_layer(), true);
+        // TODO: We shouldn't clear and then fill
+        ctx.clear(None, Color::TRANSPARENT);
+        // The image is scaled on each axis to fill the same area the rounded
+        // size covers, just like the software renderer's image.
+        ctx.transform(Affine::scale_non_uniform(
+            pixels.width / size.width,
+            pixels.height / size.height,
+        ));
+        if let Some(background) = scene.background() {
+            ctx.fill(size.to_rect(), &convert_shader(background));
+        }
+
+        render_layer(&mut ctx, scene.bottom_layer(), false);
+
+        ctx.finish()?;
+    }
+    target.to_image_buf(ImageFormat::RgbaPremul)
+}
+
+/// Renders the layout into an image without a window, exactly like it is drawn
+/// in the window. Returns the image's premultiplied RGBA pixels and the size
+/// the layout would like to have instead, if any.
+pub fn render_image(
+    state: &LayoutState,
+    [width, height]: [u32; 2],
+) -> Result<(Vec<u8>, Option<(f32, f32)>), String> {
+    let mut renderer = PietRenderer::new().map_err(|e| e.to_string())?;
+    let mut device = Device::new().map_err(|e| e.to_string())?;
+    let mut target = device
+        .bitmap_target(width as _, height as _, 1.0)
+        .map_err(|e| e.to_string())?;
+    let new_dims;
+    {
+        let mut ctx = target.render_context();
+        new_dims = renderer.render(
+            &mut ctx,
+            Size::new(width as _, height as _),
+            Scale::new(1.0, 1.0),
+            state,
+        );
+        ctx.finish().map_err(|e| e.to_string())?;
+    }
+    let image = target
+        .to_image_buf(ImageFormat::RgbaPremul)
+        .map_err(|e| e.to_string())?;
+    Ok((image.raw_pixels().to_vec(), new_dims))
 }
@@ -0,0 +1,99 @@
+//! Decides when the timer's window needs to be drawn. While an attempt is
+//! running, it's drawn every frame, up to the configured frame rate. While
+//! nothing is happening, it's only drawn once in a while, but the timer is
+//! still checked often, so starting it from a hotkey, the auto splitter or the
+//! server is noticed right away.
+
+use std::time::{Duration, Instant};
+
+use livesplit_core::{Timer, TimerPhase, TimingMethod};
+
+/// How often the window is drawn while idle.
+const IDLE_FRAME_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the timer is checked for changes while idle.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long the window keeps being drawn every frame after something changed,
+/// so that changes are never shown late.
+const ACTIVE_DURATION: Duration = Duration::from_secs(1);
+
+/// When to check again whether the window needs to be drawn.
+pub enum Wake {
+    AnimFrame,
+    After(Duration),
+}
+
+#[derive(Default)]
+pub struct RedrawScheduler {
+    last_frame: Option<Instant>,
+    active_until: Option<Instant>,
+    last_state: Option<TimerState>,
+}
+
+/// The parts of the timer's state that can change without the timer running,
+/// for example through hotkeys.
+#[derive(PartialEq)]
+struct TimerState {
+    phase: TimerPhase,
+    split_index: Option<usize>,
+    timing_method: TimingMethod,
+    comparison: String,
+}
+
+impl TimerState {
+    fn of(timer: &Timer) -> Self {
+        Self {
+            phase: timer.current_phase(),
+            split_index: timer.current_split_index(),
+            timing_method: timer.current_timing_method(),
+            comparison: timer.current_comparison().to_owned(),
+        }
+    }
+}
+
+impl RedrawScheduler {
+    /// Draws every frame for a while, for example after input.
+    pub fn wake(&mut self) {
+        self.active_until = Some(Instant::now() + ACTIVE_DURATION);
+    }
+
+    /// Returns whether the window needs to be drawn now, and when to check
+    /// again. An editor that is open keeps the window active, as its changes
+    /// are shown in the window.
+    pub fn poll(&mut self, timer: &Timer, max_fps: Option<u32>, editing: bool) -> (bool, Wake) {
+        let now = Instant::now();
+        let state = TimerState::of(timer);
+        if self.last_state.as_ref() != Some(&state) {
+            self.last_state = Some(state);
+            self.wake();
+        }
+
+        let active = editing
+            || timer.current_phase() == TimerPhase::Running
+            || self.active_until.map_or(false, |until| now < until);
+        let frame_interval = if active {
+            max_fps.map_or(Duration::ZERO, |fps| Duration::from_secs(1) / fps.max(1))
+        } else {
+            IDLE_FRAME_INTERVAL
+        };
+
+        let since_last_frame = self.last_frame.map(|last| now - last);
+        let draw = since_last_frame.map_or(true, |elapsed| elapsed >= frame_interval);
+        if draw {
+            self.last_frame = Some(now);
+        }
+
+        let wake = if !active {
+            Wake::After(IDLE_POLL_INTERVAL)
+        } else if frame_interval.is_zero() {
+            Wake::AnimFrame
+        } else {
+            let elapsed = if draw {
+                Duration::ZERO
+            } else {
+                since_last_frame.unwrap_or_default()
+            };
+            Wake::After(frame_interval - elapsed)
+        };
+        (draw, wake)
+    }
+}
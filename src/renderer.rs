@@ -0,0 +1,86 @@
+//! The backends the timer's window can be drawn with. The software renderer
+//! is what the others fall back to, so the `software-rendering` feature is
+//! required. The piet renderer is only available if the `piet-rendering`
+//! feature is enabled.
+
+use druid::{PaintCtx, Scale, Size};
+use livesplit_core::layout::LayoutState;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "piet-rendering")]
+use crate::piet_renderer::PietRenderer;
+use crate::software_renderer::SoftwareRenderer;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    #[default]
+    Software,
+    Piet,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Software => "software",
+            Backend::Piet => "piet",
+        }
+    }
+}
+
+pub enum Renderer {
+    Software(SoftwareRenderer),
+    #[cfg(feature = "piet-rendering")]
+    Piet(PietRenderer),
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Renderer::Software(SoftwareRenderer::default())
+    }
+}
+
+/// The size in physical pixels the layout is rendered at, so it stays sharp on
+/// scaled displays. Both renderers need to agree on it, so they look the same.
+pub fn physical_size(size: Size, scale: Scale) -> Size {
+    Size::new(
+        (size.width * scale.x()).round(),
+        (size.height * scale.y()).round(),
+    )
+}
+
+impl Renderer {
+    /// Creates a renderer of the backend, falling back to the software
+    /// renderer if the backend isn't available.
+    pub fn new(backend: Backend) -> Self {
+        match backend {
+            Backend::Software => {}
+            #[cfg(feature = "piet-rendering")]
+            Backend::Piet => match PietRenderer::new() {
+                Ok(renderer) => return Renderer::Piet(renderer),
+                Err(e) => log::warn!(
+                    "Failed to set up the piet renderer, using the software renderer instead: {}",
+                    e
+                ),
+            },
+            #[cfg(not(feature = "piet-rendering"))]
+            Backend::Piet => log::warn!(
+                "The piet renderer isn't included in this build, using the software renderer \
+                instead"
+            ),
+        }
+        Renderer::default()
+    }
+
+    pub fn render_scene(
+        &mut self,
+        paint_ctx: &mut PaintCtx,
+        state: &LayoutState,
+    ) -> Option<(f32, f32)> {
+        match self {
+            Renderer::Software(renderer) => renderer.render_scene(paint_ctx, state),
+            #[cfg(feature = "piet-rendering")]
+            Renderer::Piet(renderer) => renderer.render_scene(paint_ctx, state),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! The hotkey system resets the timer directly, so it can't ask the user
+//! whether to keep the new best times. While resets need to be confirmed, the
+//! reset hotkey is taken out of the hotkey system and registered here instead,
+//! where it asks the timer's window to reset.
+
+use druid::{ExtEventSink, Target};
+use livesplit_core::hotkey::{Hook, KeyCode};
+
+use crate::timer_form::RESET_HOTKEY_PRESSED;
+
+#[derive(Default)]
+pub struct ResetHotkey {
+    hook: Option<Hook>,
+    sink: Option<ExtEventSink>,
+    key: Option<KeyCode>,
+    registered: bool,
+}
+
+impl ResetHotkey {
+    /// The hotkey can only be registered once the application is launched.
+    pub fn set_sink(&mut self, sink: ExtEventSink) {
+        self.sink = Some(sink);
+        self.register();
+    }
+
+    /// Sets the key that asks to reset the timer. This is `None` whenever the
+    /// hotkey system handles resets itself.
+    pub fn set_key(&mut self, key: Option<KeyCode>) {
+        self.unregister();
+        self.key = key;
+        self.register();
+    }
+
+    fn register(&mut self) {
+        let (key, sink) = match (self.key, &self.sink) {
+            (Some(key), Some(sink)) if !self.registered => (key, sink.clone()),
+            _ => return,
+        };
+
+        if self.hook.is_none() {
+            match Hook::new() {
+                Ok(hook) => self.hook = Some(hook),
+                Err(e) => {
+                    log::error!("Failed to create the reset hotkey hook: {}", e);
+                    return;
+                }
+            }
+        }
+
+        let hook = self.hook.as_ref().unwrap();
+        let result = hook.register(key, move || {
+            let _ = sink.submit_command(RESET_HOTKEY_PRESSED, (), Target::Auto);
+        });
+        match result {
+            Ok(()) => self.registered = true,
+            Err(e) => log::error!("Failed to register the reset hotkey: {}", e),
+        }
+    }
+
+    fn unregister(&mut self) {
+        if let (Some(hook), Some(key), true) = (&self.hook, self.key, self.registered) {
+            let _ = hook.unregister(key);
+        }
+        self.registered = false;
+    }
+}
@@ -0,0 +1,228 @@
+//! A server that speaks the text protocol of the LiveSplit Server component,
+//! so external tools can control the timer. Every command is a single line.
+//! Commands that change the timer are forwarded to the UI thread, where they
+//! are handled like the context menu's commands. Queries are answered directly
+//! from the shared timer.
+
+use std::{
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread,
+};
+
+use druid::{ExtEventSink, Selector, Target};
+use livesplit_core::{
+    analysis::{current_pace, state_helper},
+    comparison,
+    timing::formatter::{Delta, Regular, TimeFormatter},
+    SharedTimer, TimeSpan, Timer, TimerPhase, TimingMethod,
+};
+
+use crate::timer_form::{
+    CONTEXT_MENU_RESET, CONTEXT_MENU_SET_COMPARISON, CONTEXT_MENU_SKIP_SPLIT,
+    CONTEXT_MENU_START_OR_SPLIT, CONTEXT_MENU_UNDO_SPLIT,
+};
+
+/// Commands that don't have an equivalent in the context menu.
+pub enum TimerCommand {
+    Start,
+    Split,
+    Pause,
+    Resume,
+    InitializeGameTime,
+    SetGameTime(TimeSpan),
+    SetLoadingTimes(TimeSpan),
+    PauseGameTime,
+    ResumeGameTime,
+    SetTimingMethod(TimingMethod),
+}
+
+pub const TIMER_COMMAND: Selector<TimerCommand> = Selector::new("server-timer-command");
+
+impl TimerCommand {
+    pub fn apply(&self, timer: &mut Timer) {
+        match self {
+            TimerCommand::Start => timer.start(),
+            TimerCommand::Split => timer.split(),
+            TimerCommand::Pause => timer.pause(),
+            TimerCommand::Resume => timer.resume(),
+            TimerCommand::InitializeGameTime => timer.initialize_game_time(),
+            TimerCommand::SetGameTime(time) => timer.set_game_time(*time),
+            TimerCommand::SetLoadingTimes(time) => timer.set_loading_times(*time),
+            TimerCommand::PauseGameTime => timer.pause_game_time(),
+            TimerCommand::ResumeGameTime => timer.resume_game_time(),
+            TimerCommand::SetTimingMethod(method) => timer.set_current_timing_method(*method),
+        }
+    }
+}
+
+pub fn start(address: SocketAddr, timer: SharedTimer, sink: ExtEventSink) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    log::info!("Server listening on {}", address);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let timer = timer.clone();
+                    let sink = sink.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = handle_connection(stream, &timer, &sink) {
+                            log::warn!("Server connection closed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => log::warn!("Failed to accept server connection: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    timer: &SharedTimer,
+    sink: &ExtEventSink,
+) -> io::Result<()> {
+    let reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+
+        if let Some(response) = handle_command(command, argument.trim(), timer, sink) {
+            writer.write_all(response.as_bytes())?;
+            writer.write_all(b"\r\n")?;
+            writer.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles a single command, returning the response for queries.
+fn handle_command(
+    command: &str,
+    argument: &str,
+    timer: &SharedTimer,
+    sink: &ExtEventSink,
+) -> Option<String> {
+    let submit = |command: TimerCommand| {
+        let _ = sink.submit_command(TIMER_COMMAND, command, Target::Auto);
+    };
+    let parse_time = || argument.parse::<TimeSpan>().ok();
+
+    match command {
+        "starttimer" => submit(TimerCommand::Start),
+        "startorsplit" => {
+            let _ = sink.submit_command(CONTEXT_MENU_START_OR_SPLIT, (), Target::Auto);
+        }
+        "split" => submit(TimerCommand::Split),
+        "unsplit" => {
+            let _ = sink.submit_command(CONTEXT_MENU_UNDO_SPLIT, (), Target::Auto);
+        }
+        "skipsplit" => {
+            let _ = sink.submit_command(CONTEXT_MENU_SKIP_SPLIT, (), Target::Auto);
+        }
+        "pause" => submit(TimerCommand::Pause),
+        "resume" => submit(TimerCommand::Resume),
+        "reset" => {
+            let _ = sink.submit_command(CONTEXT_MENU_RESET, (), Target::Auto);
+        }
+        "initgametime" => submit(TimerCommand::InitializeGameTime),
+        "setgametime" => submit(TimerCommand::SetGameTime(parse_time()?)),
+        "setloadingtimes" => submit(TimerCommand::SetLoadingTimes(parse_time()?)),
+        "pausegametime" => submit(TimerCommand::PauseGameTime),
+        "unpausegametime" => submit(TimerCommand::ResumeGameTime),
+        "setcomparison" => {
+            let _ = sink.submit_command(
+                CONTEXT_MENU_SET_COMPARISON,
+                argument.to_owned(),
+                Target::Auto,
+            );
+        }
+        "switchto" => submit(TimerCommand::SetTimingMethod(match argument {
+            "realtime" => TimingMethod::RealTime,
+            "gametime" => TimingMethod::GameTime,
+            _ => return None,
+        })),
+        "ping" => return Some("pong".into()),
+        _ => return handle_query(command, argument, &timer.read().ok()?),
+    }
+    None
+}
+
+fn handle_query(command: &str, argument: &str, timer: &Timer) -> Option<String> {
+    let method = timer.current_timing_method();
+    let comparison = if argument.is_empty() {
+        timer.current_comparison()
+    } else {
+        argument
+    };
+    let split_index = timer.current_split_index();
+    let segments = timer.run().segments();
+    let previous_segment = split_index
+        .and_then(|index| index.checked_sub(1))
+        .and_then(|index| segments.get(index));
+
+    let response = match command {
+        "getdelta" => {
+            let delta = split_index
+                .and_then(|index| state_helper::last_delta(timer.run(), index, comparison, method));
+            format_delta(delta)
+        }
+        "getlastsplittime" => format_time(previous_segment.and_then(|s| s.split_time()[method])),
+        "getcomparisonsplittime" => format_time(
+            split_index
+                .and_then(|index| segments.get(index))
+                .and_then(|s| s.comparison(comparison)[method]),
+        ),
+        "getcurrenttime" => format_time(timer.current_time()[method]),
+        "getcurrentrealtime" => format_time(timer.current_time().real_time),
+        "getcurrentgametime" => format_time(timer.current_time().game_time),
+        "getfinaltime" => format_time(segments.last().and_then(|s| {
+            if timer.current_phase() == TimerPhase::Ended {
+                s.split_time()[method]
+            } else {
+                s.comparison(comparison)[method]
+            }
+        })),
+        "getpredictedtime" => format_time(current_pace::calculate(&timer.snapshot(), comparison).0),
+        "getbestpossibletime" => format_time(
+            current_pace::calculate(&timer.snapshot(), comparison::best_segments::NAME).0,
+        ),
+        "getsplitindex" => split_index.map_or(-1, |index| index as isize).to_string(),
+        "getcurrentsplitname" => split_index
+            .and_then(|index| segments.get(index))
+            .map_or("-", |s| s.name())
+            .to_owned(),
+        "getprevioussplitname" => previous_segment.map_or("-", |s| s.name()).to_owned(),
+        "getcurrenttimerphase" => format!("{:?}", timer.current_phase()),
+        _ => {
+            log::warn!("Unknown server command: {}", command);
+            return None;
+        }
+    };
+
+    Some(response)
+}
+
+fn format_time(time: Option<TimeSpan>) -> String {
+    match time {
+        Some(time) => Regular::new().format(time).to_string(),
+        None => String::from("-"),
+    }
+}
+
+fn format_delta(time: Option<TimeSpan>) -> String {
+    match time {
+        Some(time) => Delta::new().format(time).to_string(),
+        None => String::from("-"),
+    }
+}
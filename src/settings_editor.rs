@@ -12,7 +12,7 @@ use livesplit_core::{
 
 use crate::{
     consts::{BUTTON_SPACING, DIALOG_BUTTON_HEIGHT, DIALOG_BUTTON_WIDTH, MARGIN},
-    settings_table::{self, SettingsRow},
+    settings_table::{self, RowKind, SettingsRow},
 };
 
 #[derive(Clone, Data)]
@@ -40,6 +40,7 @@ impl ListIter<SettingsRow> for State {
             index: 0,
             text: String::new(),
             value: Value::Bool(false),
+            kind: RowKind::Value,
         };
 
         for (index, field) in self.state.fields.iter().enumerate() {
@@ -55,6 +56,7 @@ impl ListIter<SettingsRow> for State {
             index: 0,
             text: String::new(),
             value: Value::Bool(false),
+            kind: RowKind::Value,
         };
 
         let mut editor = self.editor.borrow_mut();
@@ -1,4 +1,4 @@
-use std::mem;
+use std::{mem, rc::Rc};
 
 use crate::{
     combo_box,
@@ -29,6 +29,7 @@ pub struct SettingsRow {
     pub index: usize,
     pub text: String,
     pub value: Value,
+    pub kind: RowKind,
 }
 
 impl Data for SettingsRow {
@@ -37,6 +38,29 @@ impl Data for SettingsRow {
     }
 }
 
+/// Determines how a row is presented. Most rows edit their value based on its
+/// type, but auto splitters can also provide headings and choices between
+/// options that are only known at runtime.
+#[derive(Clone, PartialEq)]
+pub enum RowKind {
+    Value,
+    Title,
+    /// The value is a [`Value::String`] holding the key of the chosen option.
+    Choice(Rc<[ChoiceOption]>),
+}
+
+impl Data for RowKind {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct ChoiceOption {
+    pub key: String,
+    pub description: String,
+}
+
 struct SettingsRowWidget<T> {
     inner: T,
 }
@@ -114,8 +138,10 @@ pub fn widget<T: ListIter<SettingsRow>>() -> impl Widget<T> {
                 .with_spacer(GRID_BORDER)
                 .with_flex_child(
                     ViewSwitcher::new(
-                        |row: &SettingsRow, _| mem::discriminant(&row.value),
+                        |row: &SettingsRow, _| (row.kind.clone(), mem::discriminant(&row.value)),
                         |_, row, _| match row.value {
+                            _ if row.kind == RowKind::Title => Box::new(Flex::row()),
+                            _ if matches!(row.kind, RowKind::Choice(_)) => Box::new(choice()),
                             Value::Bool(_) => Box::new(
                                 Switch::new()
                                     .lens(Identity.map(
@@ -267,6 +293,49 @@ pub fn widget<T: ListIter<SettingsRow>>() -> impl Widget<T> {
     .border(BUTTON_BORDER, 1.0)
 }
 
+fn choice() -> impl Widget<SettingsRow> {
+    ViewSwitcher::new(
+        |row: &SettingsRow, _| row.kind.clone(),
+        |kind, _, _| {
+            let options = match kind {
+                RowKind::Choice(options) if !options.is_empty() => options.clone(),
+                // The combo box needs at least one option to show.
+                _ => return Box::new(Flex::row()),
+            };
+            let descriptions = options
+                .iter()
+                .map(|option| option.description.clone())
+                .collect();
+
+            Box::new(
+                combo_box::owned_widget(descriptions)
+                    .lens(Identity.map(
+                        {
+                            let options = options.clone();
+                            move |row: &SettingsRow| match &row.value {
+                                Value::String(v) => options
+                                    .iter()
+                                    .position(|option| &option.key == v)
+                                    .unwrap_or_default(),
+                                // TODO: What
+                                _ => 0,
+                            }
+                        },
+                        move |row: &mut SettingsRow, value: usize| {
+                            if let (Value::String(v), Some(option)) =
+                                (&mut row.value, options.get(value))
+                            {
+                                v.clone_from(&option.key);
+                            }
+                        },
+                    ))
+                    .expand_width()
+                    .center(),
+            )
+        },
+    )
+}
+
 fn hotkey() -> impl Widget<SettingsRow> {
     hotkey_button::widget().lens(Identity.map(
         |row: &SettingsRow| {
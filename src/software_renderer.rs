@@ -7,35 +7,47 @@ use druid::{
 };
 use livesplit_core::{layout::LayoutState, rendering::software::Renderer};
 
-pub fn render_scene(
-    paint_ctx: &mut PaintCtx,
-    renderer: &mut Renderer,
-    state: &LayoutState,
-) -> Option<(f32, f32)> {
-    let size = paint_ctx.size();
-    let (width, height) = (size.width as u32, size.height as u32);
-    // let dimensions = renderer.image().dimensions();
-
-    let new_dims = renderer.render(state, [width, height]);
-
-    // PietImage doesnt currently have an api to reuse previous buffers (or i couldnt find one)
-    // so just unconditionally make a new one for now
-    // TODO find a way to solve the above
-
-    let image = paint_ctx
-        .make_image(
-            width as usize,
-            height as usize,
-            renderer.image_data(),
-            ImageFormat::RgbaPremul,
-        )
-        .ok()?;
-
-    paint_ctx.draw_image(
-        &image,
-        Rect::from_origin_size(Point::ZERO, size),
-        InterpolationMode::NearestNeighbor,
-    );
-
-    new_dims
+use crate::renderer;
+
+/// Renders the layout with livesplit-core's software renderer. Piet can
+/// neither update an existing image nor upload just the regions that changed,
+/// so every frame is uploaded in full. Telling whether a frame changed would
+/// take about as long as uploading it, and the window is only drawn when a
+/// frame is due anyway.
+#[derive(Default)]
+pub struct SoftwareRenderer {
+    renderer: Renderer,
+}
+
+impl SoftwareRenderer {
+    pub fn render_scene(
+        &mut self,
+        paint_ctx: &mut PaintCtx,
+        state: &LayoutState,
+    ) -> Option<(f32, f32)> {
+        let size = paint_ctx.size();
+        let scale = paint_ctx.window().get_scale().unwrap_or_default();
+        let pixels = renderer::physical_size(size, scale);
+        let (width, height) = (pixels.width as u32, pixels.height as u32);
+
+        let new_dims = self.renderer.render(state, [width, height]);
+
+        let image = paint_ctx
+            .make_image(
+                width as usize,
+                height as usize,
+                self.renderer.image_data(),
+                ImageFormat::RgbaPremul,
+            )
+            .ok()?;
+
+        paint_ctx.draw_image(
+            &image,
+            Rect::from_origin_size(Point::ZERO, size),
+            InterpolationMode::NearestNeighbor,
+        );
+
+        // The window is sized in logical units.
+        new_dims.map(|(width, height)| (width / scale.x() as f32, height / scale.y() as f32))
+    }
 }
@@ -1,81 +1,313 @@
 use std::{
-    fs::File,
-    io::{BufReader, Read, Seek, SeekFrom},
+    io, mem,
+    path::{Path, PathBuf},
+    sync::{Once, PoisonError},
 };
 
 use druid::{
-    commands,
-    piet::{Device, ImageFormat, PietImage},
-    theme,
+    commands, theme,
     widget::{Controller, Flex},
-    AppDelegate, AppLauncher, BoxConstraints, DelegateCtx, Env, Event, EventCtx, FileDialogOptions,
-    FileInfo, FileSpec, LayoutCtx, LifeCycle, LifeCycleCtx, LocalizedString, Menu, MenuItem,
-    MouseButton, Point, RenderContext, Selector, Size, UpdateCtx, Widget, WidgetExt, WindowDesc,
-    WindowId, WindowLevel,
+    AppDelegate, AppLauncher, BoxConstraints, Command, DelegateCtx, Env, Event, EventCtx,
+    FileDialogOptions, FileInfo, FileSpec, Handled, LayoutCtx, LifeCycle, LifeCycleCtx, Menu,
+    MenuItem, MouseButton, Point, Selector, Size, Target, TimerToken, UpdateCtx, Widget, WidgetExt,
+    WindowDesc, WindowHandle, WindowId, WindowLevel,
 };
 use livesplit_core::{
-    layout::{self, LayoutSettings},
-    run::parser::composite,
-    Layout, LayoutEditor, RunEditor,
+    layout::Layout,
+    run::parser::TimerKind,
+    timing::formatter::{Regular, TimeFormatter},
+    LayoutEditor, Run, RunEditor, TimeSpan, Timer, TimerPhase,
 };
 
 use crate::{
+    attempt_recovery::{self, Attempt},
+    auto_splitter_debug, auto_splitter_settings, backups,
+    config::{Config, FileKind},
     consts::{
-        BACKGROUND, BUTTON_BORDER, BUTTON_BORDER_RADIUS, BUTTON_BOTTOM, BUTTON_TOP, MARGIN,
-        PRIMARY_LIGHT, SELECTED_TEXT_BACKGROUND_COLOR, TEXTBOX_BACKGROUND,
+        BACKGROUND, BUTTON_BORDER, BUTTON_BORDER_RADIUS, BUTTON_BOTTOM, BUTTON_TOP, PRIMARY_LIGHT,
+        SELECTED_TEXT_BACKGROUND_COLOR, TEXTBOX_BACKGROUND,
     },
-    layout_editor, run_editor, settings_editor, software_renderer, LayoutEditorLens, MainState,
-    OpenWindow, RunEditorLens, SettingsEditorLens,
+    dialog::{self, DialogButton},
+    file_watcher, layout_editor,
+    redraw::{RedrawScheduler, Wake},
+    renderer::{Backend, Renderer},
+    run_editor, server, settings_editor, AutoSplitterDebugLens, AutoSplitterSettingsEditorLens,
+    LayoutData, LayoutEditorLens, MainState, OpenWindow, RunEditorLens, SettingsEditorLens,
 };
 
 struct WithMenu<T> {
-    // device: Device,
-    renderer: livesplit_core::rendering::software::Renderer,
+    renderer: Renderer,
+    /// The backend the renderer was created for. The renderer may have fallen
+    /// back to another one.
+    render_backend: Backend,
+    redraw: RedrawScheduler,
+    redraw_timer: TimerToken,
+    /// Whether the layout state was updated since the window was last drawn.
+    layout_state_updated: bool,
+    watch_timer: TimerToken,
+    recovery_timer: TimerToken,
+    /// The attempt that was in progress when the timer last exited, until the
+    /// user decides what to do with it.
+    recovered_attempt: Option<Attempt>,
+    attempt_saver: attempt_recovery::Saver,
+    attempt_saved: bool,
     inner: T,
 }
 
 impl<T> WithMenu<T> {
     fn new(inner: T) -> Self {
-        // let mut device = Device::new().unwrap();
         Self {
-            // bottom_image: {
-            //     let mut target = device.bitmap_target(1, 1, 1.0).unwrap();
-            //     let mut ctx = target.render_context();
-            //     let image = ctx
-            //         .make_image(1, 1, &[0; 4], ImageFormat::RgbaPremul)
-            //         .unwrap();
-            //     ctx.finish().unwrap();
-            //     image
-            // },
-            // device,
-            renderer: Default::default(),
+            renderer: Renderer::default(),
+            render_backend: Backend::Software,
+            redraw: RedrawScheduler::default(),
+            redraw_timer: TimerToken::INVALID,
+            layout_state_updated: false,
+            watch_timer: TimerToken::INVALID,
+            recovery_timer: TimerToken::INVALID,
+            recovered_attempt: None,
+            attempt_saver: attempt_recovery::Saver::new(),
+            attempt_saved: false,
             inner,
         }
     }
+
+    fn schedule_redraw(&mut self, ctx: &mut EventCtx, data: &MainState) {
+        // The timer is checked here often enough to notice the restored
+        // attempt ending before the next one is started.
+        put_back_offset(data);
+        let max_fps = data.config.borrow().max_fps();
+        let (draw, wake) = self
+            .redraw
+            .poll(&data.timer.read().unwrap(), max_fps, is_editing(data));
+        if draw {
+            // The clients are updated here rather than when drawing, as the
+            // window isn't drawn while it's minimized or hidden.
+            let mut layout_data = data.layout_data.borrow_mut();
+            update_layout_state(data, &mut layout_data);
+            data.web_socket
+                .publish(&data.timer.read().unwrap(), &layout_data.layout_state);
+            self.layout_state_updated = true;
+            ctx.request_paint();
+        }
+        match wake {
+            Wake::AnimFrame => ctx.request_anim_frame(),
+            Wake::After(duration) => self.redraw_timer = ctx.request_timer(duration),
+        }
+    }
+
+    /// Keeps the attempt in progress on disk, and removes it once it's over.
+    /// Only what's needed is taken from the timer, the writing happens in the
+    /// background.
+    fn save_attempt(&mut self, data: &MainState) {
+        put_back_offset(data);
+
+        let timer = data.timer.read().unwrap();
+        if timer.current_phase() == TimerPhase::NotRunning {
+            if self.attempt_saved {
+                self.attempt_saver.remove();
+                self.attempt_saved = false;
+            }
+            return;
+        }
+        let splits = data
+            .config
+            .borrow()
+            .current_file(FileKind::Splits)
+            .map(Path::to_path_buf);
+        self.attempt_saver
+            .save(&timer, splits, data.restored_offset.get());
+        self.attempt_saved = true;
+    }
+
+    /// Continues the recovered attempt, or records it in the splits' history.
+    fn recover_attempt(&mut self, ctx: &mut EventCtx, data: &mut MainState, restore: bool) {
+        let attempt = match self.recovered_attempt.take() {
+            Some(attempt) => attempt,
+            None => return,
+        };
+        if let Some(splits) = &attempt.splits {
+            let current = data.config.borrow().current_file(FileKind::Splits) == Some(splits);
+            // The attempt can't be dealt with without its splits. Its files
+            // are kept, so it is offered again on the next start.
+            if !current && !open_splits(ctx, data, splits) {
+                return;
+            }
+        }
+
+        let mut timer = data.timer.write().unwrap();
+        if restore {
+            data.restored_offset.set(attempt.restore(&mut timer));
+        } else if let Some(mut run) = attempt.recorded_splits {
+            run.mark_as_modified();
+            if timer.set_run(run).is_err() {
+                ctx.submit_command(dialog::show_error(
+                    "Failed to Record Attempt",
+                    "The splits don't contain any segments.",
+                ));
+            }
+        } else {
+            ctx.submit_command(dialog::show_error(
+                "Failed to Record Attempt",
+                "The splits with the attempt couldn't be loaded.",
+            ));
+        }
+        // A continued attempt is saved again with the next save.
+        self.attempt_saver.remove();
+        self.attempt_saved = false;
+    }
+}
+
+/// Asks the user what to do with an attempt that was in progress when the
+/// timer last exited.
+fn recovered_attempt_dialog(attempt: &Attempt) -> WindowDesc<MainState> {
+    dialog::window(
+        "Unfinished Attempt",
+        format!(
+            "The timer exited during an attempt of {} - {}, started {}, that was {} splits in \
+            at {}. Do you want to continue the attempt, record it in the history of the \
+            splits, or discard it?",
+            attempt.game,
+            attempt.category,
+            attempt.started.format("%Y-%m-%d %H:%M"),
+            attempt.current_split_index,
+            Regular::new().format(Some(TimeSpan::from_seconds(attempt.real_time))),
+        ),
+        vec![
+            DialogButton::new("Continue", RESTORE_ATTEMPT),
+            DialogButton::new("Record", RECORD_ATTEMPT),
+            DialogButton::new("Discard", DISCARD_ATTEMPT),
+        ],
+    )
 }
 
 const CONTEXT_MENU_EDIT_SPLITS: Selector = Selector::new("context-menu-edit-splits");
 const CONTEXT_MENU_OPEN_SPLITS: Selector<FileInfo> = Selector::new("context-menu-open-splits");
+const CONTEXT_MENU_OPEN_RECENT_SPLITS: Selector<PathBuf> =
+    Selector::new("context-menu-open-recent-splits");
+const CONTEXT_MENU_OPEN_RECENT_LAYOUT: Selector<PathBuf> =
+    Selector::new("context-menu-open-recent-layout");
+const CONTEXT_MENU_TOGGLE_PINNED: Selector<(FileKind, PathBuf)> =
+    Selector::new("context-menu-toggle-pinned");
+const CONTEXT_MENU_CLEAR_RECENT: Selector<FileKind> = Selector::new("context-menu-clear-recent");
+const CONTEXT_MENU_SWITCH_PROFILE: Selector<ProfileSwitch> =
+    Selector::new("context-menu-switch-profile");
+const SWITCH_PROFILE_DISCARDING_CHANGES: Selector<ProfileSwitch> =
+    Selector::new("switch-profile-discarding-changes");
+const CONTEXT_MENU_DUPLICATE_PROFILE: Selector = Selector::new("context-menu-duplicate-profile");
+const CONTEXT_MENU_RENAME_PROFILE: Selector = Selector::new("context-menu-rename-profile");
+const RENAME_PROFILE: Selector<String> = Selector::new("rename-profile");
+const CONTEXT_MENU_TOGGLE_ALWAYS_ON_TOP: Selector =
+    Selector::new("context-menu-toggle-always-on-top");
+const RESTORE_ATTEMPT: Selector = Selector::new("restore-attempt");
+const RECORD_ATTEMPT: Selector = Selector::new("record-attempt");
+const DISCARD_ATTEMPT: Selector = Selector::new("discard-attempt");
+const RELOAD_FILE: Selector<FileKind> = Selector::new("reload-file");
+const SAVE_COPY_AND_RELOAD: Selector<FileKind> = Selector::new("save-copy-and-reload");
+const CONTEXT_MENU_RESTORE_BACKUP: Selector = Selector::new("context-menu-restore-backup");
+const CONTEXT_MENU_SAVE_SPLITS: Selector = Selector::new("context-menu-save-splits");
+const CONTEXT_MENU_SAVE_SPLITS_AS: Selector<FileInfo> =
+    Selector::new("context-menu-save-splits-as");
+const CONTEXT_MENU_SAVE_LAYOUT: Selector = Selector::new("context-menu-save-layout");
+const CONTEXT_MENU_SAVE_LAYOUT_AS: Selector<FileInfo> =
+    Selector::new("context-menu-save-layout-as");
+const SAVE_AND_QUIT: Selector = Selector::new("save-and-quit");
+const SAVE_SPLITS_AS_AND_QUIT: Selector<FileInfo> = Selector::new("save-splits-as-and-quit");
+const SAVE_LAYOUT_AS_AND_QUIT: Selector<FileInfo> = Selector::new("save-layout-as-and-quit");
+const QUIT_DISCARDING_CHANGES: Selector = Selector::new("quit-discarding-changes");
 const CONTEXT_MENU_EDIT_LAYOUT: Selector = Selector::new("context-menu-edit-layout");
 const CONTEXT_MENU_OPEN_LAYOUT: Selector<FileInfo> = Selector::new("context-menu-open-layout");
-const CONTEXT_MENU_START_OR_SPLIT: Selector = Selector::new("context-menu-start-or-split");
-const CONTEXT_MENU_RESET: Selector = Selector::new("context-menu-reset");
-const CONTEXT_MENU_UNDO_SPLIT: Selector = Selector::new("context-menu-undo-split");
-const CONTEXT_MENU_SKIP_SPLIT: Selector = Selector::new("context-menu-skip-split");
-const CONTEXT_MENU_TOGGLE_PAUSE: Selector = Selector::new("context-menu-toggle-pause");
-const CONTEXT_MENU_UNDO_ALL_PAUSES: Selector = Selector::new("context-menu-undo-all-pauses");
-const CONTEXT_MENU_TOGGLE_TIMING_METHOD: Selector =
+pub(crate) const CONTEXT_MENU_START_OR_SPLIT: Selector =
+    Selector::new("context-menu-start-or-split");
+pub(crate) const CONTEXT_MENU_RESET: Selector = Selector::new("context-menu-reset");
+pub(crate) const CONTEXT_MENU_UNDO_SPLIT: Selector = Selector::new("context-menu-undo-split");
+pub(crate) const CONTEXT_MENU_SKIP_SPLIT: Selector = Selector::new("context-menu-skip-split");
+pub(crate) const CONTEXT_MENU_TOGGLE_PAUSE: Selector = Selector::new("context-menu-toggle-pause");
+pub(crate) const CONTEXT_MENU_UNDO_ALL_PAUSES: Selector =
+    Selector::new("context-menu-undo-all-pauses");
+pub(crate) const CONTEXT_MENU_TOGGLE_TIMING_METHOD: Selector =
     Selector::new("context-menu-toggle-timing-method");
-const CONTEXT_MENU_SET_COMPARISON: Selector<String> = Selector::new("context-menu-set-comparison");
+pub(crate) const CONTEXT_MENU_SET_COMPARISON: Selector<String> =
+    Selector::new("context-menu-set-comparison");
+/// Sent by the reset hotkey while resets need to be confirmed.
+pub(crate) const RESET_HOTKEY_PRESSED: Selector = Selector::new("reset-hotkey-pressed");
+const RESET_SAVING_TIMES: Selector = Selector::new("reset-saving-times");
+const RESET_DISCARDING_TIMES: Selector = Selector::new("reset-discarding-times");
+const CONTEXT_MENU_TOGGLE_CONFIRM_RESETS: Selector =
+    Selector::new("context-menu-toggle-confirm-resets");
 const CONTEXT_MENU_EDIT_SETTINGS: Selector = Selector::new("context-menu-edit-settings");
+const CONTEXT_MENU_OPEN_AUTO_SPLITTER: Selector<FileInfo> =
+    Selector::new("context-menu-open-auto-splitter");
+const CONTEXT_MENU_UNLOAD_AUTO_SPLITTER: Selector =
+    Selector::new("context-menu-unload-auto-splitter");
+const CONTEXT_MENU_EDIT_AUTO_SPLITTER_SETTINGS: Selector =
+    Selector::new("context-menu-edit-auto-splitter-settings");
+const CONTEXT_MENU_OPEN_AUTO_SPLITTER_DEBUG: Selector =
+    Selector::new("context-menu-open-auto-splitter-debug");
 
 impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
     fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut MainState, env: &Env) {
+        if matches!(
+            event,
+            Event::MouseDown(_)
+                | Event::MouseUp(_)
+                | Event::Wheel(_)
+                | Event::KeyDown(_)
+                | Event::Command(_)
+        ) {
+            // Input and commands may change what is shown.
+            self.redraw.wake();
+            ctx.request_paint();
+        }
+
         match event {
-            Event::AnimFrame(_) => {
-                ctx.request_anim_frame();
-                ctx.request_paint();
+            Event::WindowConnected => {
+                if let Some(error) = data.config.borrow_mut().take_load_error() {
+                    ctx.submit_command(dialog::show_error("Invalid Configuration", error));
+                }
+                for (title, error) in data.config.borrow_mut().take_open_errors() {
+                    ctx.submit_command(dialog::show_error(title, error));
+                }
+                if data.config.borrow().always_on_top() {
+                    set_always_on_top(ctx.window(), true);
+                }
+                // The auto splitter is loaded once the window is there, so
+                // failing to load it can be reported.
+                if let Err(e) = data
+                    .config
+                    .borrow_mut()
+                    .maybe_load_auto_splitter(&data.auto_splitter, &data.timer)
+                {
+                    ctx.submit_command(auto_splitter_error(e));
+                }
+                if let Some(attempt) = attempt_recovery::load() {
+                    ctx.new_window(recovered_attempt_dialog(&attempt));
+                    self.recovered_attempt = Some(attempt);
+                }
+            }
+            Event::WindowSize(size) => {
+                data.config.borrow_mut().set_window_size(*size);
+            }
+            Event::WindowCloseRequested => {
+                if let Some(dialog) = unsaved_changes_dialog(data) {
+                    ctx.set_handled();
+                    ctx.new_window(dialog);
+                    return;
+                }
+                let mut config = data.config.borrow_mut();
+                config.set_window_position(ctx.window().get_position());
+                config.remember_timer_settings(&data.timer.read().unwrap());
+                let _ = config.save();
+            }
+            Event::Timer(token) if *token == self.watch_timer => {
+                self.watch_timer = ctx.request_timer(file_watcher::POLL_INTERVAL);
+                check_external_changes(ctx, data);
             }
+            Event::Timer(token) if *token == self.recovery_timer => {
+                self.recovery_timer = ctx.request_timer(attempt_recovery::SAVE_INTERVAL);
+                self.save_attempt(data);
+            }
+            Event::AnimFrame(_) => self.schedule_redraw(ctx, data),
+            Event::Timer(token) if *token == self.redraw_timer => self.schedule_redraw(ctx, data),
             Event::Wheel(event) => {
                 if event.wheel_delta.y > 0.0 {
                     data.layout_data.borrow_mut().layout.scroll_down();
@@ -84,15 +316,12 @@ impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
                 }
             }
             Event::MouseUp(event) => {
-                if event.button == MouseButton::Right
-                    && data.run_editor.is_none()
-                    && data.layout_editor.is_none()
-                    && data.settings_editor.is_none()
-                {
+                if event.button == MouseButton::Right && !is_editing(data) {
                     let mut compare_against = Menu::new("Compare Against");
 
-                    //TODO dont unwrap
-                    let timer = data.timer.read().unwrap();
+                    // The menu is still useful if something panicked while
+                    // holding the lock, as the timer's state is only read.
+                    let timer = data.timer.read().unwrap_or_else(PoisonError::into_inner);
                     let current_comparison = timer.current_comparison();
                     for comparison in timer.run().comparisons() {
                         compare_against = compare_against.entry(
@@ -102,6 +331,88 @@ impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
                         );
                     }
 
+                    let config = data.config.borrow();
+                    let auto_splitter = Menu::new("Auto Splitter")
+                        .entry(
+                            MenuItem::new("Load Auto Splitter...")
+                                .command(
+                                    commands::SHOW_OPEN_PANEL.with(
+                                        FileDialogOptions::new()
+                                            .title("Load Auto Splitter")
+                                            .allowed_types(vec![
+                                                FileSpec {
+                                                    name: "WebAssembly Auto Splitters",
+                                                    extensions: &["wasm"],
+                                                },
+                                                FileSpec {
+                                                    name: "All Files",
+                                                    extensions: &["*.*"],
+                                                },
+                                            ])
+                                            .accept_command(CONTEXT_MENU_OPEN_AUTO_SPLITTER),
+                                    ),
+                                )
+                                // Auto splitters are associated with the splits file.
+                                .enabled(config.has_splits_path()),
+                        )
+                        .entry(
+                            MenuItem::new("Auto Splitter Settings...")
+                                .command(CONTEXT_MENU_EDIT_AUTO_SPLITTER_SETTINGS)
+                                // The settings are stored in the splits, which
+                                // can't be replaced during an attempt.
+                                .enabled(
+                                    data.auto_splitter.settings_widgets().is_some()
+                                        && timer.current_phase() == TimerPhase::NotRunning,
+                                ),
+                        )
+                        .entry(
+                            MenuItem::new("Unload Auto Splitter")
+                                .command(CONTEXT_MENU_UNLOAD_AUTO_SPLITTER)
+                                .enabled(config.auto_splitter_path().is_some()),
+                        )
+                        .separator()
+                        .entry(
+                            MenuItem::new("Debug Window...")
+                                .command(CONTEXT_MENU_OPEN_AUTO_SPLITTER_DEBUG)
+                                .enabled(data.auto_splitter_debug.is_none()),
+                        );
+
+                    let mut profiles = Menu::new("Profiles");
+                    let current_profile = config.profile();
+                    let profile_names = config.profile_names();
+                    for &name in &profile_names {
+                        profiles = profiles.entry(
+                            MenuItem::new(name)
+                                .command(CONTEXT_MENU_SWITCH_PROFILE.with(ProfileSwitch {
+                                    to: name.to_owned(),
+                                    delete_current: false,
+                                }))
+                                .selected(name == current_profile),
+                        );
+                    }
+                    let other_profile = profile_names
+                        .iter()
+                        .find(|&&name| name != current_profile)
+                        .map(|&name| name.to_owned());
+                    profiles = profiles
+                        .separator()
+                        .entry(
+                            MenuItem::new("Duplicate Current Profile")
+                                .command(CONTEXT_MENU_DUPLICATE_PROFILE),
+                        )
+                        .entry(
+                            MenuItem::new("Rename Current Profile...")
+                                .command(CONTEXT_MENU_RENAME_PROFILE),
+                        )
+                        .entry(
+                            MenuItem::new("Delete Current Profile")
+                                .command(CONTEXT_MENU_SWITCH_PROFILE.with(ProfileSwitch {
+                                    to: other_profile.clone().unwrap_or_default(),
+                                    delete_current: true,
+                                }))
+                                .enabled(other_profile.is_some()),
+                        );
+
                     ctx.show_context_menu::<MainState>(
                         Menu::new("LiveSplit")
                             .entry(
@@ -116,25 +427,20 @@ impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
                                     ),
                                 ),
                             )
-                            .entry(MenuItem::new("Save Splits").command(
-                                commands::SHOW_SAVE_PANEL.with(
-                                    FileDialogOptions::new().title("Save Splits").allowed_types(
-                                        vec![
-                                            FileSpec {
-                                                name: "LiveSplit Splits",
-                                                extensions: &["lss"],
-                                            },
-                                            FileSpec {
-                                                name: "All Files",
-                                                extensions: &["*.*"],
-                                            },
-                                        ],
-                                    ),
-                                ),
+                            .entry(recent_files_menu(
+                                "Open Recent Splits",
+                                FileKind::Splits,
+                                &config,
+                                CONTEXT_MENU_OPEN_RECENT_SPLITS,
                             ))
+                            .entry(MenuItem::new("Save Splits").command(CONTEXT_MENU_SAVE_SPLITS))
                             .entry(
-                                MenuItem::new("Save Splits As...")
-                                    .command(CONTEXT_MENU_EDIT_SPLITS),
+                                MenuItem::new("Save Splits As...").command(
+                                    commands::SHOW_SAVE_PANEL.with(
+                                        save_splits_dialog_options()
+                                            .accept_command(CONTEXT_MENU_SAVE_SPLITS_AS),
+                                    ),
+                                ),
                             )
                             .separator()
                             .entry(
@@ -163,9 +469,16 @@ impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
                                     .entry(
                                         MenuItem::new("Toggle Timing Method")
                                             .command(CONTEXT_MENU_TOGGLE_TIMING_METHOD),
+                                    )
+                                    .separator()
+                                    .entry(
+                                        MenuItem::new("Ask Before Discarding Best Times")
+                                            .command(CONTEXT_MENU_TOGGLE_CONFIRM_RESETS)
+                                            .selected(config.confirm_resets()),
                                     ),
                             )
                             .entry(compare_against)
+                            .entry(auto_splitter)
                             .separator()
                             .entry(
                                 MenuItem::new("Edit Layout...").command(CONTEXT_MENU_EDIT_LAYOUT),
@@ -189,12 +502,33 @@ impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
                                     ),
                                 ),
                             )
-                            .entry(MenuItem::new("Save Layout").command(CONTEXT_MENU_EDIT_SPLITS))
+                            .entry(recent_files_menu(
+                                "Open Recent Layout",
+                                FileKind::Layout,
+                                &config,
+                                CONTEXT_MENU_OPEN_RECENT_LAYOUT,
+                            ))
+                            .entry(MenuItem::new("Save Layout").command(CONTEXT_MENU_SAVE_LAYOUT))
+                            .entry(
+                                MenuItem::new("Save Layout As...").command(
+                                    commands::SHOW_SAVE_PANEL.with(
+                                        save_layout_dialog_options()
+                                            .accept_command(CONTEXT_MENU_SAVE_LAYOUT_AS),
+                                    ),
+                                ),
+                            )
+                            .separator()
                             .entry(
-                                MenuItem::new("Save Layout As...")
-                                    .command(CONTEXT_MENU_EDIT_SPLITS),
+                                MenuItem::new("Restore Backup...")
+                                    .command(CONTEXT_MENU_RESTORE_BACKUP),
                             )
                             .separator()
+                            .entry(profiles)
+                            .entry(
+                                MenuItem::new("Always on Top (If Allowed)")
+                                    .command(CONTEXT_MENU_TOGGLE_ALWAYS_ON_TOP)
+                                    .selected(config.always_on_top()),
+                            )
                             .entry(MenuItem::new("Settings").command(CONTEXT_MENU_EDIT_SETTINGS))
                             .separator()
                             // .entry(MenuItem::new("About").command(CONTEXT_MENU_EDIT_SPLITS))
@@ -207,9 +541,18 @@ impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
                 if command.is(CONTEXT_MENU_EDIT_SPLITS) {
                     // the only error is threadstopped which means the hotkey system is effectively disabled anyways
                     let _ = data.hotkey_system.borrow_mut().deactivate();
-                    // TODO dont unwrap
                     let run = data.timer.read().unwrap().run().clone();
-                    let editor = RunEditor::new(run).unwrap();
+                    let editor = match RunEditor::new(run) {
+                        Ok(editor) => editor,
+                        Err(e) => {
+                            data.hotkey_system.borrow_mut().activate();
+                            ctx.submit_command(dialog::show_error(
+                                "Failed to Edit Splits",
+                                format!("The splits can't be edited: {}", e),
+                            ));
+                            return;
+                        }
+                    };
                     let window = WindowDesc::new(run_editor::root_widget().lens(RunEditorLens))
                         .title("Splits Editor")
                         .with_min_size((690.0, 495.0))
@@ -222,29 +565,45 @@ impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
                         state: run_editor::State::new(editor),
                     });
                 } else if let Some(file_info) = command.get(CONTEXT_MENU_OPEN_SPLITS) {
-                    let mut file = File::open(file_info.path()).unwrap();
-                    let mut file_contents = Vec::new();
-                    let _size = file.read_to_end(&mut file_contents);
-                    let run = composite::parse(
-                        file_contents.as_slice(),
-                        Some(file_info.path().to_path_buf()),
-                        true,
-                    )
-                    .unwrap();
-                    //TODO dont unwrap
-                    data.timer
-                        .write()
-                        .unwrap()
-                        .set_run(run.run)
-                        .map_err(drop)
-                        .unwrap();
-                    data.config
-                        .borrow_mut()
-                        .set_splits_path(Some(file_info.path()));
+                    open_splits(ctx, data, file_info.path());
+                } else if let Some(path) = command.get(CONTEXT_MENU_OPEN_RECENT_SPLITS) {
+                    open_splits(ctx, data, path);
+                } else if command.is(CONTEXT_MENU_SAVE_SPLITS) {
+                    save_splits(ctx, data, false);
+                } else if let Some(file_info) = command.get(CONTEXT_MENU_SAVE_SPLITS_AS) {
+                    save_splits_as(ctx, data, file_info.path(), false);
+                } else if command.is(CONTEXT_MENU_SAVE_LAYOUT) {
+                    save_layout(ctx, data, false);
+                } else if let Some(file_info) = command.get(CONTEXT_MENU_SAVE_LAYOUT_AS) {
+                    save_layout_as(ctx, data, file_info.path(), false);
+                } else if command.is(SAVE_AND_QUIT) {
+                    // Everything that has unsaved changes is saved one after
+                    // another, each one continuing with this command again.
+                    if has_unsaved_splits(data) {
+                        save_splits(ctx, data, true);
+                    } else if data.layout_data.borrow().modified {
+                        save_layout(ctx, data, true);
+                    } else {
+                        ctx.submit_command(commands::QUIT_APP);
+                    }
+                } else if let Some(file_info) = command.get(SAVE_SPLITS_AS_AND_QUIT) {
+                    save_splits_as(ctx, data, file_info.path(), true);
+                } else if let Some(file_info) = command.get(SAVE_LAYOUT_AS_AND_QUIT) {
+                    save_layout_as(ctx, data, file_info.path(), true);
                 } else if command.is(CONTEXT_MENU_EDIT_LAYOUT) {
                     data.hotkey_system.borrow_mut().deactivate();
                     let layout = data.layout_data.borrow().layout.clone();
-                    let editor = LayoutEditor::new(layout).unwrap();
+                    let editor = match LayoutEditor::new(layout) {
+                        Ok(editor) => editor,
+                        Err(e) => {
+                            data.hotkey_system.borrow_mut().activate();
+                            ctx.submit_command(dialog::show_error(
+                                "Failed to Edit Layout",
+                                format!("The layout can't be edited: {}", e),
+                            ));
+                            return;
+                        }
+                    };
                     let window =
                         WindowDesc::new(layout_editor::root_widget().lens(LayoutEditorLens))
                             .title("Layout Editor")
@@ -258,29 +617,177 @@ impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
                         state: layout_editor::State::new(editor),
                     });
                 } else if let Some(file_info) = command.get(CONTEXT_MENU_OPEN_LAYOUT) {
-                    let mut file = BufReader::new(File::open(file_info.path()).unwrap());
-                    let mut file_contents = String::new();
-                    let _size = file.read_to_string(&mut file_contents).unwrap();
-                    data.layout_data.borrow_mut().layout = if let Ok(settings) =
-                        LayoutSettings::from_json(file_contents.as_bytes())
-                    {
-                        Layout::from_settings(settings)
+                    open_layout(ctx, data, file_info.path());
+                } else if let Some(path) = command.get(CONTEXT_MENU_OPEN_RECENT_LAYOUT) {
+                    open_layout(ctx, data, path);
+                } else if let Some(switch) = command.get(CONTEXT_MENU_SWITCH_PROFILE) {
+                    if has_unsaved_splits(data) || data.layout_data.borrow().modified {
+                        ctx.new_window(dialog::window(
+                            "Switch Profile",
+                            "The profile's splits and layout replace the current ones, which \
+                             have unsaved changes. Do you want to switch anyway?",
+                            vec![
+                                DialogButton::new(
+                                    "Switch",
+                                    SWITCH_PROFILE_DISCARDING_CHANGES.with(switch.clone()),
+                                ),
+                                DialogButton::close("Cancel"),
+                            ],
+                        ));
                     } else {
-                        if let Ok(parsed_layout) = layout::parser::parse(file_contents.as_str()) {
-                            parsed_layout
-                        } else {
-                            // TODO: Maybe dangerous
-                            return;
-                        }
-                    };
-                    data.config
-                        .borrow_mut()
-                        .set_layout_path(Some(file_info.path()));
+                        switch_profile(ctx, data, switch);
+                    }
+                } else if let Some(switch) = command.get(SWITCH_PROFILE_DISCARDING_CHANGES) {
+                    switch_profile(ctx, data, switch);
+                } else if command.is(CONTEXT_MENU_DUPLICATE_PROFILE) {
+                    // The copy uses the same files, so nothing needs to be
+                    // reloaded.
+                    let mut config = data.config.borrow_mut();
+                    config.duplicate_profile();
+                    if let Some(error) = config_save_error(config.save()) {
+                        ctx.submit_command(error);
+                    }
+                } else if command.is(CONTEXT_MENU_RENAME_PROFILE) {
+                    ctx.new_window(dialog::text_input(
+                        "Rename Profile",
+                        "The new name of the current profile:",
+                        data.config.borrow().profile().to_owned(),
+                        RENAME_PROFILE,
+                    ));
+                } else if let Some(name) = command.get(RENAME_PROFILE) {
+                    let mut config = data.config.borrow_mut();
+                    if let Err(e) = config.rename_profile(name) {
+                        ctx.submit_command(dialog::show_error("Failed to Rename Profile", e));
+                    } else if let Some(error) = config_save_error(config.save()) {
+                        ctx.submit_command(error);
+                    }
+                } else if command.is(CONTEXT_MENU_TOGGLE_ALWAYS_ON_TOP) {
+                    let mut config = data.config.borrow_mut();
+                    let always_on_top = !config.always_on_top();
+                    set_always_on_top(ctx.window(), always_on_top);
+                    if let Some(error) = config_save_error(config.set_always_on_top(always_on_top))
+                    {
+                        ctx.submit_command(error);
+                    }
+                } else if command.is(RESTORE_ATTEMPT) {
+                    self.recover_attempt(ctx, data, true);
+                } else if command.is(RECORD_ATTEMPT) {
+                    self.recover_attempt(ctx, data, false);
+                } else if command.is(DISCARD_ATTEMPT) {
+                    self.recovered_attempt = None;
+                    self.attempt_saver.remove();
+                } else if let Some(&kind) = command.get(RELOAD_FILE) {
+                    reload_file(ctx, data, kind);
+                } else if let Some(&kind) = command.get(SAVE_COPY_AND_RELOAD) {
+                    if save_copy(ctx, data, kind) {
+                        reload_file(ctx, data, kind);
+                    }
+                } else if command.is(CONTEXT_MENU_RESTORE_BACKUP) {
+                    ctx.new_window(backups::window(
+                        &data.config.borrow(),
+                        ctx.get_external_handle(),
+                    ));
+                } else if let Some((kind, path)) = command.get(backups::RESTORE_BACKUP) {
+                    restore_backup(ctx, data, *kind, path);
+                } else if let Some((kind, path)) = command.get(CONTEXT_MENU_TOGGLE_PINNED) {
+                    let mut config = data.config.borrow_mut();
+                    if let Some(error) = config_save_error(config.toggle_pinned(*kind, path)) {
+                        ctx.submit_command(error);
+                    }
+                } else if let Some(&kind) = command.get(CONTEXT_MENU_CLEAR_RECENT) {
+                    let mut config = data.config.borrow_mut();
+                    if let Some(error) = config_save_error(config.clear_recent(kind)) {
+                        ctx.submit_command(error);
+                    }
+                } else if let Some(file_info) = command.get(CONTEXT_MENU_OPEN_AUTO_SPLITTER) {
+                    let mut config = data.config.borrow_mut();
+                    // Only an auto splitter that loads is remembered for the
+                    // splits.
+                    let path = file_info.path();
+                    if let Err(e) =
+                        config.load_auto_splitter(path, &data.auto_splitter, &data.timer)
+                    {
+                        ctx.submit_command(auto_splitter_error(e));
+                    } else if let Some(error) =
+                        config_save_error(config.set_auto_splitter_path(Some(path)))
+                    {
+                        ctx.submit_command(error);
+                    }
+                } else if command.is(CONTEXT_MENU_UNLOAD_AUTO_SPLITTER) {
+                    let mut config = data.config.borrow_mut();
+                    if let Some(error) = config_save_error(config.set_auto_splitter_path(None)) {
+                        ctx.submit_command(error);
+                    }
+                    // Without an auto splitter this only unloads it.
+                    let _ = config.maybe_load_auto_splitter(&data.auto_splitter, &data.timer);
+                } else if command.is(CONTEXT_MENU_EDIT_AUTO_SPLITTER_SETTINGS) {
+                    if let Some(widgets) = data.auto_splitter.settings_widgets() {
+                        data.hotkey_system.borrow_mut().deactivate();
+                        let window = WindowDesc::new(
+                            auto_splitter_settings::root_widget()
+                                .lens(AutoSplitterSettingsEditorLens),
+                        )
+                        .title("Auto Splitter Settings")
+                        .with_min_size((550.0, 400.0))
+                        .window_size((550.0, 450.0))
+                        .set_level(WindowLevel::Modal(ctx.window().clone()));
+                        let window_id = window.id;
+                        ctx.new_window(window);
+                        data.auto_splitter_settings_editor = Some(OpenWindow {
+                            id: window_id,
+                            state: auto_splitter_settings::State::new(
+                                &widgets,
+                                auto_splitter_settings::read(data.timer.read().unwrap().run()),
+                            ),
+                        });
+                    }
+                } else if command.is(CONTEXT_MENU_OPEN_AUTO_SPLITTER_DEBUG) {
+                    // The debug window is meant to be kept open next to the
+                    // game, so unlike the editors it isn't modal.
+                    let window = WindowDesc::new(
+                        auto_splitter_debug::root_widget().lens(AutoSplitterDebugLens),
+                    )
+                    .title("Auto Splitter Debug")
+                    .with_min_size((300.0, 300.0))
+                    .window_size((450.0, 550.0));
+                    let window_id = window.id;
+                    ctx.new_window(window);
+                    data.auto_splitter_debug = Some(OpenWindow {
+                        id: window_id,
+                        state: auto_splitter_debug::State::new(
+                            data.timer.clone(),
+                            data.auto_splitter_log.clone(),
+                            data.config.clone(),
+                        ),
+                    });
                 } else if command.is(CONTEXT_MENU_START_OR_SPLIT) {
                     data.timer.write().unwrap().split_or_start();
                 } else if command.is(CONTEXT_MENU_RESET) {
-                    // TODO: Ask user if they want to save best segments.
+                    reset(ctx, data);
+                } else if command.is(RESET_HOTKEY_PRESSED) {
+                    // The hotkey system ignores hotkeys while an editor is
+                    // open, so the reset hotkey does too.
+                    if !is_editing(data) {
+                        reset(ctx, data);
+                    }
+                } else if command.is(RESET_SAVING_TIMES) {
                     data.timer.write().unwrap().reset(true);
+                    put_back_offset(data);
+                } else if command.is(RESET_DISCARDING_TIMES) {
+                    data.timer.write().unwrap().reset(false);
+                    put_back_offset(data);
+                } else if command.is(CONTEXT_MENU_TOGGLE_CONFIRM_RESETS) {
+                    let mut config = data.config.borrow_mut();
+                    let confirm_resets = !config.confirm_resets();
+                    if let Some(error) =
+                        config_save_error(config.set_confirm_resets(confirm_resets))
+                    {
+                        ctx.submit_command(error);
+                    }
+                    config.configure_hotkeys(
+                        &mut data.hotkey_system.borrow_mut(),
+                        &mut data.reset_hotkey.borrow_mut(),
+                    );
                 } else if command.is(CONTEXT_MENU_UNDO_SPLIT) {
                     data.timer.write().unwrap().undo_split();
                 } else if command.is(CONTEXT_MENU_SKIP_SPLIT) {
@@ -296,6 +803,8 @@ impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
                         .write()
                         .unwrap()
                         .set_current_comparison(comparison.as_str());
+                } else if let Some(command) = command.get(server::TIMER_COMMAND) {
+                    command.apply(&mut data.timer.write().unwrap());
                 } else if command.is(CONTEXT_MENU_EDIT_SETTINGS) {
                     data.hotkey_system.borrow_mut().deactivate();
                     let window =
@@ -308,7 +817,7 @@ impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
                     ctx.new_window(window);
                     data.settings_editor = Some(OpenWindow {
                         id: window_id,
-                        state: settings_editor::State::new(data.hotkey_system.borrow().config()),
+                        state: settings_editor::State::new(data.config.borrow().hotkeys()),
                     });
                 }
             }
@@ -327,6 +836,8 @@ impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
         if let LifeCycle::WidgetAdded = event {
             ctx.request_anim_frame();
             ctx.request_paint();
+            self.watch_timer = ctx.request_timer(file_watcher::POLL_INTERVAL);
+            self.recovery_timer = ctx.request_timer(attempt_recovery::SAVE_INTERVAL);
         }
     }
 
@@ -348,65 +859,709 @@ impl<T: Widget<MainState>> Widget<MainState> for WithMenu<T> {
         let mut layout_data = data.layout_data.borrow_mut();
         let layout_data = &mut *layout_data;
 
-        if let Some(editor) = &data.layout_editor {
-            editor
-                .state
-                .editor
-                .borrow_mut()
-                .as_mut()
-                .unwrap()
-                .update_layout_state(
-                    &mut layout_data.layout_state,
-                    &data.timer.read().unwrap().snapshot(),
-                );
-        } else {
-            layout_data.layout.update_state(
-                &mut layout_data.layout_state,
-                &data.timer.read().unwrap().snapshot(),
-            );
+        // The window may also be drawn for other reasons, such as resizing.
+        if !mem::take(&mut self.layout_state_updated) {
+            update_layout_state(data, layout_data);
         }
 
-        // let size = ctx.size();
-
-        // if let Some((new_width, new_height)) = layout_data.scene_manager.update_scene(
-        //     PietResourceAllocator,
-        //     (size.width as f32, size.height as f32),
-        //     &layout_data.layout_state,
-        // ) {
-        //     ctx.window()
-        //         .set_size(Size::new(new_width as _, new_height as _));
-        // }
-
-        // software_renderer::render_scene(
-        //     ctx,
-        //     &mut self.bottom_image,
-        //     &mut self.device,
-        //     layout_data.scene_manager.scene(),
-        // );
-
-        if let Some((new_width, new_height)) = software_renderer::render_scene(
-            ctx,
-            &mut self.renderer,
-            &layout_data.layout_state,
-        ) {
+        // Switching profiles may switch the backend.
+        let backend = data.config.borrow().render_backend();
+        if backend != self.render_backend {
+            self.renderer = Renderer::new(backend);
+            self.render_backend = backend;
+        }
+
+        if let Some((new_width, new_height)) =
+            self.renderer.render_scene(ctx, &layout_data.layout_state)
+        {
             ctx.window()
                 .set_size(Size::new(new_width as _, new_height as _));
         }
     }
 }
 
+/// Returns whether the splits were opened. The user is told why if they
+/// weren't.
+fn open_splits(ctx: &mut EventCtx, data: &mut MainState, path: &Path) -> bool {
+    let parsed = match Config::parse_splits_from(path) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            ctx.submit_command(dialog::show_error(
+                "Failed to Open Splits",
+                format!("The splits {} couldn't be opened: {}", path.display(), e),
+            ));
+            return false;
+        }
+    };
+    let mut config = data.config.borrow_mut();
+    let mut timer = data.timer.write().unwrap();
+    config.remember_timer_settings(&timer);
+    if timer.set_run(parsed.run).is_err() {
+        ctx.submit_command(dialog::show_error(
+            "Failed to Open Splits",
+            "The splits don't contain any segments.",
+        ));
+        return false;
+    }
+    if let Some(error) = config_save_error(config.set_splits_path(Some(path))) {
+        ctx.submit_command(error);
+    }
+    config.set_splits_kind(Some(parsed.kind));
+    // This uses the comparison and timing method last used with the splits.
+    config.configure_timer(&mut timer);
+    drop(timer);
+    if let Err(e) = config.maybe_load_auto_splitter(&data.auto_splitter, &data.timer) {
+        ctx.submit_command(auto_splitter_error(e));
+    }
+
+    let layout = config
+        .associated_layout()
+        .filter(|&layout| config.current_file(FileKind::Layout) != Some(layout))
+        .map(Path::to_path_buf);
+    drop(config);
+    if let Some(layout) = layout {
+        // A layout with unsaved changes isn't replaced.
+        if !data.layout_data.borrow().modified {
+            open_layout(ctx, data, &layout);
+        }
+    }
+    true
+}
+
+fn open_layout(ctx: &mut EventCtx, data: &mut MainState, path: &Path) {
+    let layout = match Config::parse_layout_from(path) {
+        Ok(layout) => layout,
+        Err(e) => {
+            ctx.submit_command(dialog::show_error(
+                "Failed to Open Layout",
+                format!("The layout {} couldn't be opened: {}", path.display(), e),
+            ));
+            return;
+        }
+    };
+    let mut layout_data = data.layout_data.borrow_mut();
+    layout_data.layout = layout;
+    layout_data.modified = false;
+    drop(layout_data);
+    if let Some(error) = config_save_error(data.config.borrow_mut().set_layout_path(Some(path))) {
+        ctx.submit_command(error);
+    }
+}
+
+/// Asks for the window to be kept above all others. Some window managers don't
+/// allow this, and no backend can tell whether it was, so this is only a
+/// request.
+fn set_always_on_top(window: &WindowHandle, always_on_top: bool) {
+    static LOGGED: Once = Once::new();
+
+    window.set_always_on_top(always_on_top);
+    if always_on_top {
+        LOGGED.call_once(|| {
+            log::info!(
+                "Requested keeping the window on top. Whether the system allows it can't be \
+                checked."
+            )
+        });
+    }
+}
+
+fn update_layout_state(data: &MainState, layout_data: &mut LayoutData) {
+    let timer = data.timer.read().unwrap();
+    let snapshot = timer.snapshot();
+    if let Some(editor) = &data.layout_editor {
+        editor
+            .state
+            .editor
+            .borrow_mut()
+            .as_mut()
+            .unwrap()
+            .update_layout_state(&mut layout_data.layout_state, &snapshot);
+    } else {
+        layout_data
+            .layout
+            .update_state(&mut layout_data.layout_state, &snapshot);
+    }
+}
+
+fn is_editing(data: &MainState) -> bool {
+    data.run_editor.is_some()
+        || data.layout_editor.is_some()
+        || data.settings_editor.is_some()
+        || data.auto_splitter_settings_editor.is_some()
+}
+
+/// Our own saves change the files too, so they must not be mistaken for
+/// changes by other programs.
+fn acknowledge_saved(data: &MainState, config: &Config, kind: FileKind) {
+    data.file_watcher
+        .borrow_mut()
+        .acknowledge(kind, config.current_file(kind));
+}
+
+/// Offers to reload the splits or the layout if another program changed them.
+/// Nothing is replaced during an attempt or while an editor is open, so the
+/// changes are only looked at afterwards.
+fn check_external_changes(ctx: &mut EventCtx, data: &MainState) {
+    if is_editing(data) || data.timer.read().unwrap().current_phase() != TimerPhase::NotRunning {
+        return;
+    }
+
+    for kind in [FileKind::Splits, FileKind::Layout] {
+        let config = data.config.borrow();
+        let path = config.current_file(kind);
+        if !data.file_watcher.borrow_mut().has_changed(kind, path) {
+            continue;
+        }
+        let file_name = path
+            .and_then(|path| path.file_name())
+            .unwrap_or_default()
+            .to_string_lossy();
+        let modified = match kind {
+            FileKind::Splits => has_unsaved_splits(data),
+            FileKind::Layout => data.layout_data.borrow().modified,
+        };
+
+        let dialog = if modified {
+            dialog::window(
+                "Conflicting Changes",
+                format!(
+                    "{} was changed by another program while you have unsaved changes to it. \
+                    Do you want to keep your version, take the other one, or save yours as a \
+                    copy and then take the other one?",
+                    file_name
+                ),
+                vec![
+                    DialogButton::close("Keep Mine"),
+                    DialogButton::new("Take Theirs", RELOAD_FILE.with(kind)),
+                    DialogButton::new("Save Copy", SAVE_COPY_AND_RELOAD.with(kind)),
+                ],
+            )
+        } else {
+            dialog::window(
+                "File Changed",
+                format!(
+                    "{} was changed by another program. Do you want to reload it?",
+                    file_name
+                ),
+                vec![
+                    DialogButton::new("Reload", RELOAD_FILE.with(kind)),
+                    DialogButton::close("Ignore"),
+                ],
+            )
+        };
+        ctx.new_window(dialog);
+    }
+}
+
+/// Replaces the splits or the layout with the file's current contents. Unlike
+/// opening a file, this keeps the configuration as it is.
+fn reload_file(ctx: &mut EventCtx, data: &MainState, kind: FileKind) {
+    let mut config = data.config.borrow_mut();
+    let path = match config.current_file(kind) {
+        Some(path) => path.to_path_buf(),
+        None => return,
+    };
+    let result = match kind {
+        FileKind::Splits => Config::parse_splits_from(&path).and_then(|parsed| {
+            data.timer
+                .write()
+                .unwrap()
+                .set_run(parsed.run)
+                .map_err(|_| String::from("The splits don't contain any segments."))?;
+            config.set_splits_kind(Some(parsed.kind));
+            Ok(())
+        }),
+        FileKind::Layout => Config::parse_layout_from(&path).map(|layout| {
+            let mut layout_data = data.layout_data.borrow_mut();
+            layout_data.layout = layout;
+            layout_data.modified = false;
+        }),
+    };
+    match result {
+        Ok(()) => acknowledge_saved(data, &config, kind),
+        Err(e) => ctx.submit_command(dialog::show_error(
+            "Failed to Reload",
+            format!("{} couldn't be reloaded: {}", path.display(), e),
+        )),
+    }
+}
+
+/// Saves the splits or the layout next to the file they were opened from,
+/// without switching to the copy. Returns whether the copy was saved.
+fn save_copy(ctx: &mut EventCtx, data: &MainState, kind: FileKind) -> bool {
+    let config = data.config.borrow();
+    let path = match config.current_file(kind) {
+        Some(path) => path,
+        None => return false,
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let timestamp = chrono::Local::now().format("%Y-%m-%d %H-%M-%S");
+    let result = match kind {
+        FileKind::Splits => {
+            let copy = path.with_file_name(format!("{} (Copy {}).lss", stem, timestamp));
+            config
+                .save_splits_to(&copy, &splits_to_save(data, &data.timer.read().unwrap()))
+                .map_err(splits_save_error)
+        }
+        FileKind::Layout => {
+            let copy = path.with_file_name(format!("{} (Copy {}).ls1l", stem, timestamp));
+            config
+                .save_layout_to(&copy, &data.layout_data.borrow().layout)
+                .map_err(layout_save_error)
+        }
+    };
+    match result {
+        Ok(()) => true,
+        Err(error) => {
+            ctx.submit_command(error);
+            false
+        }
+    }
+}
+
+/// Replaces the current splits or layout with a backup. The file they were
+/// opened from is only replaced once they are saved.
+fn restore_backup(ctx: &mut EventCtx, data: &mut MainState, kind: FileKind, path: &Path) {
+    if let FileKind::Layout = kind {
+        match Config::parse_layout_from(path) {
+            Ok(layout) => {
+                let mut layout_data = data.layout_data.borrow_mut();
+                layout_data.layout = layout;
+                layout_data.modified = true;
+            }
+            Err(e) => ctx.submit_command(dialog::show_error(
+                "Failed to Restore Backup",
+                format!("The backup couldn't be opened: {}", e),
+            )),
+        }
+        return;
+    }
+
+    let result = Config::parse_run_from(path).and_then(|mut run| {
+        run.mark_as_modified();
+        data.timer
+            .write()
+            .unwrap()
+            .set_run(run)
+            .map_err(|_| String::from("The splits don't contain any segments."))
+    });
+    if let Err(e) = result {
+        ctx.submit_command(dialog::show_error(
+            "Failed to Restore Backup",
+            format!("The backup couldn't be opened: {}", e),
+        ));
+    }
+}
+
+#[derive(Clone)]
+struct ProfileSwitch {
+    to: String,
+    /// The profile that is switched away from is deleted.
+    delete_current: bool,
+}
+
+/// Switches to another profile and applies all of its settings, including its
+/// splits and layout.
+fn switch_profile(ctx: &mut EventCtx, data: &mut MainState, switch: &ProfileSwitch) {
+    let mut config = data.config.borrow_mut();
+    config.remember_timer_settings(&data.timer.read().unwrap());
+    let previous = config.profile().to_owned();
+    config.switch_profile(&switch.to);
+    if switch.delete_current {
+        config.delete_profile(&previous);
+    }
+    if let Some(error) = config_save_error(config.save()) {
+        ctx.submit_command(error);
+    }
+
+    // The profile's files are opened like any others, so failing to open them
+    // is reported rather than quietly replaced by the defaults.
+    let mut run = None;
+    if let Some(path) = config.current_file(FileKind::Splits).map(Path::to_path_buf) {
+        match Config::parse_splits_from(&path) {
+            Ok(parsed) if !parsed.run.is_empty() => {
+                config.set_splits_kind(Some(parsed.kind));
+                run = Some(parsed.run);
+            }
+            result => {
+                let error = result
+                    .err()
+                    .unwrap_or_else(|| String::from("The splits don't contain any segments."));
+                ctx.submit_command(dialog::show_error(
+                    "Failed to Open Splits",
+                    format!(
+                        "The splits {} couldn't be opened: {}",
+                        path.display(),
+                        error
+                    ),
+                ));
+            }
+        }
+    }
+    {
+        let mut timer = data.timer.write().unwrap();
+        let run = run.unwrap_or_else(|| {
+            config.set_splits_kind(None);
+            Config::default_run()
+        });
+        // Only splits with segments are used, so this can't fail.
+        let _ = timer.set_run(run);
+        config.configure_timer(&mut timer);
+    }
+    config.configure_hotkeys(
+        &mut data.hotkey_system.borrow_mut(),
+        &mut data.reset_hotkey.borrow_mut(),
+    );
+    if let Err(e) = config.maybe_load_auto_splitter(&data.auto_splitter, &data.timer) {
+        ctx.submit_command(auto_splitter_error(e));
+    }
+
+    let mut layout_data = data.layout_data.borrow_mut();
+    layout_data.layout = match config.current_file(FileKind::Layout) {
+        Some(path) => Config::parse_layout_from(path).unwrap_or_else(|e| {
+            ctx.submit_command(dialog::show_error(
+                "Failed to Open Layout",
+                format!("The layout {} couldn't be opened: {}", path.display(), e),
+            ));
+            Layout::default_layout()
+        }),
+        None => Layout::default_layout(),
+    };
+    layout_data.modified = false;
+
+    let window = ctx.window();
+    let (width, height) = config.window_size();
+    window.set_size(Size::new(width, height));
+    if let Some(position) = config.window_position() {
+        window.set_position(position);
+    }
+    set_always_on_top(window, config.always_on_top());
+}
+
+/// Lists the pinned and the recently opened files of a kind, so they can be
+/// opened again without a file dialog.
+fn recent_files_menu(
+    title: &str,
+    kind: FileKind,
+    config: &Config,
+    open: Selector<PathBuf>,
+) -> Menu<MainState> {
+    let recent_files = config.recent_files(kind);
+    let mut menu = Menu::new(title);
+
+    for path in recent_files.pinned() {
+        menu = menu.entry(recent_file_entry(path, open));
+    }
+    if !recent_files.pinned().is_empty() {
+        menu = menu.separator();
+    }
+    for path in recent_files.recent() {
+        menu = menu.entry(recent_file_entry(path, open));
+    }
+    if !recent_files.recent().is_empty() {
+        menu = menu.separator();
+    }
+
+    // Any of the files can be pinned, including the current one, which may
+    // not be in the list if it was opened from the command line.
+    let mut pinned_menu = Menu::new("Pinned Files");
+    let mut files: Vec<&Path> = recent_files
+        .pinned()
+        .iter()
+        .chain(recent_files.recent())
+        .map(PathBuf::as_path)
+        .collect();
+    if let Some(current) = config.current_file(kind) {
+        if !files.contains(&current) {
+            files.insert(0, current);
+        }
+    }
+    for &path in &files {
+        pinned_menu = pinned_menu.entry(
+            MenuItem::new(file_name(path))
+                .command(CONTEXT_MENU_TOGGLE_PINNED.with((kind, path.to_path_buf())))
+                .selected(recent_files.is_pinned(path)),
+        );
+    }
+
+    menu.entry(pinned_menu.enabled(!files.is_empty())).entry(
+        MenuItem::new("Clear Recent Files")
+            .command(CONTEXT_MENU_CLEAR_RECENT.with(kind))
+            .enabled(!recent_files.recent().is_empty()),
+    )
+}
+
+fn recent_file_entry(path: &Path, open: Selector<PathBuf>) -> MenuItem<MainState> {
+    MenuItem::new(file_name(path)).command(open.with(path.to_path_buf()))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// Puts the splits' original offset back once the restored attempt is over, so
+/// the next attempt doesn't start at the restored attempt's time.
+fn put_back_offset(data: &MainState) {
+    if let Some(offset) = data.restored_offset.get() {
+        if offset.put_back(&mut data.timer.write().unwrap()) {
+            data.restored_offset.set(None);
+        }
+    }
+}
+
+/// Resets the timer. If that would update the run with new best times, the
+/// user is asked whether to keep them, unless they chose not to be asked.
+fn reset(ctx: &mut EventCtx, data: &MainState) {
+    let mut timer = data.timer.write().unwrap();
+    if !data.config.borrow().confirm_resets() || !timer.current_attempt_has_new_best_times() {
+        timer.reset(true);
+        drop(timer);
+        put_back_offset(data);
+        return;
+    }
+
+    ctx.new_window(dialog::window(
+        "Reset",
+        "This attempt has new best times. Do you want to update your splits with them?",
+        vec![
+            DialogButton::new("Save", RESET_SAVING_TIMES),
+            DialogButton::new("Discard", RESET_DISCARDING_TIMES),
+            DialogButton::close("Cancel"),
+        ],
+    ));
+}
+
+fn save_splits_dialog_options() -> FileDialogOptions {
+    FileDialogOptions::new()
+        .title("Save Splits")
+        .allowed_types(vec![
+            FileSpec {
+                name: "LiveSplit Splits",
+                extensions: &["lss"],
+            },
+            FileSpec {
+                name: "All Files",
+                extensions: &["*.*"],
+            },
+        ])
+        .default_type(FileSpec {
+            name: "LiveSplit Splits",
+            extensions: &["lss"],
+        })
+}
+
+fn save_layout_dialog_options() -> FileDialogOptions {
+    FileDialogOptions::new()
+        .title("Save Layout")
+        .allowed_types(vec![
+            FileSpec {
+                name: "LiveSplit One Layouts",
+                extensions: &["ls1l"],
+            },
+            FileSpec {
+                name: "All Files",
+                extensions: &["*.*"],
+            },
+        ])
+        .default_type(FileSpec {
+            name: "LiveSplit One Layouts",
+            extensions: &["ls1l"],
+        })
+}
+
+fn has_unsaved_splits(data: &MainState) -> bool {
+    data.timer.read().unwrap().run().has_been_modified()
+}
+
+/// Asks the user whether to save the splits and the layout before exiting, if
+/// either of them has unsaved changes.
+fn unsaved_changes_dialog(data: &MainState) -> Option<WindowDesc<MainState>> {
+    let what = match (has_unsaved_splits(data), data.layout_data.borrow().modified) {
+        (true, true) => "Your splits and your layout have",
+        (true, false) => "Your splits have",
+        (false, true) => "Your layout has",
+        (false, false) => return None,
+    };
+    Some(dialog::window(
+        "Unsaved Changes",
+        format!(
+            "{} been changed but not yet saved. Do you want to save the changes before exiting?",
+            what
+        ),
+        vec![
+            DialogButton::new("Save", SAVE_AND_QUIT),
+            DialogButton::new("Don't Save", QUIT_DISCARDING_CHANGES),
+            DialogButton::close("Cancel"),
+        ],
+    ))
+}
+
+fn splits_to_save(data: &MainState, timer: &Timer) -> Run {
+    attempt_recovery::run_to_save(timer, data.restored_offset.get())
+}
+
+/// Saves the splits to the file they were opened from. If they don't have one
+/// yet, the user is asked for one.
+fn save_splits(ctx: &mut EventCtx, data: &MainState, quit: bool) {
+    let config = data.config.borrow();
+    if !config.can_save_splits() {
+        let accept_command = if quit {
+            SAVE_SPLITS_AS_AND_QUIT
+        } else {
+            CONTEXT_MENU_SAVE_SPLITS_AS
+        };
+        ctx.submit_command(
+            commands::SHOW_SAVE_PANEL
+                .with(save_splits_dialog_options().accept_command(accept_command)),
+        );
+        return;
+    }
+
+    let mut timer = data.timer.write().unwrap();
+    match config.save_splits(&splits_to_save(data, &timer)) {
+        Ok(()) => {
+            timer.mark_as_unmodified();
+            acknowledge_saved(data, &config, FileKind::Splits);
+            if quit {
+                ctx.submit_command(SAVE_AND_QUIT);
+            }
+        }
+        Err(e) => ctx.submit_command(splits_save_error(e)),
+    }
+}
+
+fn save_splits_as(ctx: &mut EventCtx, data: &MainState, path: &Path, quit: bool) {
+    {
+        let mut timer = data.timer.write().unwrap();
+        if let Err(e) = data
+            .config
+            .borrow()
+            .save_splits_to(path, &splits_to_save(data, &timer))
+        {
+            ctx.submit_command(splits_save_error(e));
+            return;
+        }
+        timer.mark_as_unmodified();
+    }
+
+    let mut config = data.config.borrow_mut();
+    if let Some(error) = config_save_error(config.set_splits_path(Some(path))) {
+        ctx.submit_command(error);
+    }
+    config.set_splits_kind(Some(TimerKind::LiveSplit));
+    acknowledge_saved(data, &config, FileKind::Splits);
+    if quit {
+        ctx.submit_command(SAVE_AND_QUIT);
+    }
+}
+
+/// Saves the layout to the file it was opened from. If it doesn't have one
+/// yet, the user is asked for one.
+fn save_layout(ctx: &mut EventCtx, data: &MainState, quit: bool) {
+    let config = data.config.borrow();
+    if !config.can_save_layout() {
+        let accept_command = if quit {
+            SAVE_LAYOUT_AS_AND_QUIT
+        } else {
+            CONTEXT_MENU_SAVE_LAYOUT_AS
+        };
+        ctx.submit_command(
+            commands::SHOW_SAVE_PANEL
+                .with(save_layout_dialog_options().accept_command(accept_command)),
+        );
+        return;
+    }
+
+    let mut layout_data = data.layout_data.borrow_mut();
+    match config.save_layout(&layout_data.layout) {
+        Ok(()) => {
+            layout_data.modified = false;
+            acknowledge_saved(data, &config, FileKind::Layout);
+            if quit {
+                ctx.submit_command(SAVE_AND_QUIT);
+            }
+        }
+        Err(e) => ctx.submit_command(layout_save_error(e)),
+    }
+}
+
+fn save_layout_as(ctx: &mut EventCtx, data: &MainState, path: &Path, quit: bool) {
+    {
+        let mut layout_data = data.layout_data.borrow_mut();
+        if let Err(e) = data
+            .config
+            .borrow()
+            .save_layout_to(path, &layout_data.layout)
+        {
+            ctx.submit_command(layout_save_error(e));
+            return;
+        }
+        layout_data.modified = false;
+    }
+
+    if let Some(error) = config_save_error(data.config.borrow_mut().set_layout_path(Some(path))) {
+        ctx.submit_command(error);
+    }
+    acknowledge_saved(data, &data.config.borrow(), FileKind::Layout);
+    if quit {
+        ctx.submit_command(SAVE_AND_QUIT);
+    }
+}
+
+fn layout_save_error(e: io::Error) -> Command {
+    dialog::show_error(
+        "Failed to Save Layout",
+        format!("The layout couldn't be saved: {}", e),
+    )
+}
+
+fn splits_save_error(e: io::Error) -> Command {
+    dialog::show_error(
+        "Failed to Save Splits",
+        format!("The splits couldn't be saved: {}", e),
+    )
+}
+
+/// Returns the command telling the user that the configuration couldn't be
+/// saved, if that's the case.
+fn config_save_error(result: io::Result<()>) -> Option<Command> {
+    let e = result.err()?;
+    Some(dialog::show_error(
+        "Failed to Save Settings",
+        format!("Your settings couldn't be saved: {}", e),
+    ))
+}
+
+fn auto_splitter_error(message: String) -> Command {
+    dialog::show_error("Failed to Load Auto Splitter", message)
+}
+
 struct DragWindowController {
     init_pos: Option<Point>,
+    moved: bool,
 }
 
 impl DragWindowController {
     pub fn new() -> Self {
-        DragWindowController { init_pos: None }
+        DragWindowController {
+            init_pos: None,
+            moved: false,
+        }
     }
 }
 
-impl<T, W: Widget<T>> Controller<T, W> for DragWindowController {
-    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+impl<W: Widget<MainState>> Controller<MainState, W> for DragWindowController {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut MainState,
+        env: &Env,
+    ) {
         match event {
             Event::MouseDown(me) if me.buttons.has_left() => {
                 ctx.set_active(true);
@@ -418,12 +1573,21 @@ impl<T, W: Widget<T>> Controller<T, W> for DragWindowController {
                     let within_window_change = me.window_pos.to_vec2() - init_pos.to_vec2();
                     let old_pos = window.get_position();
                     let new_pos = old_pos + within_window_change;
-                    window.set_position(new_pos)
+                    window.set_position(new_pos);
+                    self.moved = true;
                 }
             }
             Event::MouseUp(_me) if ctx.is_active() => {
                 self.init_pos = None;
-                ctx.set_active(false)
+                ctx.set_active(false);
+                if self.moved {
+                    self.moved = false;
+                    let mut config = data.config.borrow_mut();
+                    config.set_window_position(ctx.window().get_position());
+                    // Failing to save is already logged and not worth
+                    // interrupting the user over after every drag.
+                    let _ = config.save();
+                }
             }
             _ => (),
         }
@@ -435,9 +1599,64 @@ pub fn root_widget() -> impl Widget<MainState> {
     WithMenu::new(Flex::row()).controller(DragWindowController::new())
 }
 
-struct WindowManagement;
+struct WindowManagement {
+    /// The user chose to exit without saving their changes.
+    discard_changes: bool,
+    main_window_id: WindowId,
+    /// The timer's window, so its position can be remembered on exit.
+    main_window: Option<WindowHandle>,
+}
 
 impl AppDelegate<MainState> for WindowManagement {
+    fn command(
+        &mut self,
+        ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        data: &mut MainState,
+        _env: &Env,
+    ) -> Handled {
+        if let Some(error) = cmd.get(dialog::SHOW_ERROR) {
+            ctx.new_window(dialog::error(&error.title, error.message.clone()));
+            return Handled::Yes;
+        }
+        if cmd.is(QUIT_DISCARDING_CHANGES) {
+            self.discard_changes = true;
+            ctx.submit_command(commands::QUIT_APP);
+            return Handled::Yes;
+        }
+        if cmd.is(commands::QUIT_APP) {
+            if !self.discard_changes {
+                if let Some(dialog) = unsaved_changes_dialog(data) {
+                    ctx.new_window(dialog);
+                    return Handled::Yes;
+                }
+            }
+            // The window's size is kept up to date in the configuration, but
+            // it may have been moved by the system rather than by dragging.
+            let mut config = data.config.borrow_mut();
+            if let Some(window) = &self.main_window {
+                config.set_window_position(window.get_position());
+            }
+            config.remember_timer_settings(&data.timer.read().unwrap());
+            let _ = config.save();
+        }
+        Handled::No
+    }
+
+    fn window_added(
+        &mut self,
+        id: WindowId,
+        handle: WindowHandle,
+        _data: &mut MainState,
+        _env: &Env,
+        _ctx: &mut DelegateCtx,
+    ) {
+        if id == self.main_window_id {
+            self.main_window = Some(handle);
+        }
+    }
+
     fn window_removed(
         &mut self,
         id: WindowId,
@@ -448,13 +1667,14 @@ impl AppDelegate<MainState> for WindowManagement {
         if let Some(window) = &data.run_editor {
             if id == window.id {
                 if window.state.closed_with_ok {
-                    let run = window.state.editor.borrow_mut().take().unwrap().close();
-                    data.timer
-                        .write()
-                        .unwrap()
-                        .set_run(run)
-                        .map_err(drop)
-                        .unwrap();
+                    let mut run = window.state.editor.borrow_mut().take().unwrap().close();
+                    run.mark_as_modified();
+                    if data.timer.write().unwrap().set_run(run).is_err() {
+                        ctx.submit_command(dialog::show_error(
+                            "Failed to Edit Splits",
+                            "The splits don't contain any segments.",
+                        ));
+                    }
                 }
                 data.run_editor = None;
                 data.hotkey_system.borrow_mut().activate();
@@ -466,7 +1686,9 @@ impl AppDelegate<MainState> for WindowManagement {
             if id == window.id {
                 if window.state.closed_with_ok {
                     let layout = window.state.editor.borrow_mut().take().unwrap().close();
-                    data.layout_data.borrow_mut().layout = layout;
+                    let mut layout_data = data.layout_data.borrow_mut();
+                    layout_data.layout = layout;
+                    layout_data.modified = true;
                 }
                 data.layout_editor = None;
                 data.hotkey_system.borrow_mut().activate();
@@ -478,19 +1700,71 @@ impl AppDelegate<MainState> for WindowManagement {
             if id == window.id {
                 if window.state.closed_with_ok {
                     let hotkey_config = window.state.editor.borrow_mut().take().unwrap();
-                    data.hotkey_system.borrow_mut().set_config(hotkey_config);
-                    data.config.borrow_mut().set_hotkeys(hotkey_config);
+                    let mut config = data.config.borrow_mut();
+                    if let Some(error) = config_save_error(config.set_hotkeys(hotkey_config)) {
+                        ctx.submit_command(error);
+                    }
+                    config.configure_hotkeys(
+                        &mut data.hotkey_system.borrow_mut(),
+                        &mut data.reset_hotkey.borrow_mut(),
+                    );
                 }
                 data.settings_editor = None;
                 data.hotkey_system.borrow_mut().activate();
                 return;
             }
         }
+
+        if let Some(window) = &data.auto_splitter_debug {
+            if id == window.id {
+                data.auto_splitter_debug = None;
+                return;
+            }
+        }
+
+        if let Some(window) = &data.auto_splitter_settings_editor {
+            if id == window.id {
+                if window.state.closed_with_ok {
+                    let settings = window.state.editor.borrow_mut().take().unwrap();
+                    let mut timer = data.timer.write().unwrap();
+                    if timer.current_phase() == TimerPhase::NotRunning {
+                        let mut run = timer.run().clone();
+                        auto_splitter_settings::write(&mut run, &settings);
+                        run.mark_as_modified();
+                        // The run is the timer's, so it always has segments.
+                        let _ = timer.set_run(run);
+                    } else {
+                        ctx.submit_command(dialog::show_error(
+                            "Failed to Store Auto Splitter Settings",
+                            "The settings can't be stored in the splits during an attempt. \
+                            They only apply until the auto splitter is loaded again.",
+                        ));
+                    }
+                    drop(timer);
+                    auto_splitter_settings::apply(&data.auto_splitter, &settings);
+                }
+                data.auto_splitter_settings_editor = None;
+                data.hotkey_system.borrow_mut().activate();
+                return;
+            }
+        }
     }
 }
 
 pub fn launch(state: MainState, window: WindowDesc<MainState>) {
-    AppLauncher::with_window(window)
+    let main_window_id = window.id;
+    let launcher = AppLauncher::with_window(window);
+    {
+        let config = state.config.borrow();
+        config.maybe_start_server(&state.timer, launcher.get_external_handle());
+        config.maybe_start_web_socket(&state.web_socket, launcher.get_external_handle());
+    }
+    state
+        .reset_hotkey
+        .borrow_mut()
+        .set_sink(launcher.get_external_handle());
+
+    launcher
         .configure_env(|env, _| {
             env.set(
                 theme::SELECTED_TEXT_BACKGROUND_COLOR,
@@ -504,7 +1778,11 @@ pub fn launch(state: MainState, window: WindowDesc<MainState>) {
             env.set(theme::PRIMARY_LIGHT, PRIMARY_LIGHT);
             env.set(theme::BUTTON_BORDER_RADIUS, BUTTON_BORDER_RADIUS);
         })
-        .delegate(WindowManagement)
+        .delegate(WindowManagement {
+            discard_changes: false,
+            main_window_id,
+            main_window: None,
+        })
         .launch(state)
         .unwrap();
 }
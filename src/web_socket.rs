@@ -0,0 +1,233 @@
+//! A WebSocket endpoint for overlays and external controllers. Every connected
+//! client receives a JSON message with the timer's state and the layout state
+//! whenever it changes. Clients can send JSON commands that mirror the context
+//! menu's actions, such as `{ "command": "startOrSplit" }`.
+
+use std::{
+    io::{self, ErrorKind},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use druid::{ExtEventSink, Target};
+use livesplit_core::{layout::LayoutState, Timer, TimerPhase};
+use serde::{Deserialize, Serialize};
+use tungstenite::{Message, WebSocket};
+
+use crate::timer_form::{
+    CONTEXT_MENU_RESET, CONTEXT_MENU_SET_COMPARISON, CONTEXT_MENU_SKIP_SPLIT,
+    CONTEXT_MENU_START_OR_SPLIT, CONTEXT_MENU_TOGGLE_PAUSE, CONTEXT_MENU_TOGGLE_TIMING_METHOD,
+    CONTEXT_MENU_UNDO_ALL_PAUSES, CONTEXT_MENU_UNDO_SPLIT,
+};
+
+/// How long a client waits for a command before it checks for new updates.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The sending half for the connected clients. It lives on the UI thread,
+/// which is where the layout state is calculated.
+#[derive(Clone, Default)]
+pub struct Clients {
+    senders: Arc<Mutex<Vec<Sender<Arc<str>>>>>,
+    last_update: Arc<Mutex<Option<Arc<str>>>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Update<'a> {
+    phase: &'static str,
+    split_index: Option<usize>,
+    current_split: Option<&'a str>,
+    timing_method: &'static str,
+    comparison: &'a str,
+    /// The difference between each split time and the comparison, in seconds.
+    deltas: Vec<Option<f64>>,
+    layout: &'a LayoutState,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "camelCase")]
+enum Command {
+    StartOrSplit,
+    Reset,
+    UndoSplit,
+    SkipSplit,
+    TogglePause,
+    UndoAllPauses,
+    ToggleTimingMethod,
+    SetComparison { comparison: String },
+}
+
+impl Clients {
+    /// Sends the current state to all clients, unless it didn't change since
+    /// the last update.
+    pub fn publish(&self, timer: &Timer, layout_state: &LayoutState) {
+        let mut senders = match self.senders.lock() {
+            Ok(senders) => senders,
+            Err(_) => return,
+        };
+        if senders.is_empty() {
+            return;
+        }
+
+        let message: Arc<str> = match serde_json::to_string(&update(timer, layout_state)) {
+            Ok(message) => message.into(),
+            Err(e) => {
+                log::error!("Failed to serialize WebSocket update: {}", e);
+                return;
+            }
+        };
+
+        if let Ok(mut last_update) = self.last_update.lock() {
+            if last_update.as_deref() == Some(&*message) {
+                return;
+            }
+            *last_update = Some(message.clone());
+        }
+
+        senders.retain(|sender| sender.send(message.clone()).is_ok());
+    }
+
+    fn connect(&self) -> Receiver<Arc<str>> {
+        let (sender, receiver) = mpsc::channel();
+        if let Ok(last_update) = self.last_update.lock() {
+            if let Some(last_update) = &*last_update {
+                let _ = sender.send(last_update.clone());
+            }
+        }
+        if let Ok(mut senders) = self.senders.lock() {
+            senders.push(sender);
+        }
+        receiver
+    }
+}
+
+fn update<'a>(timer: &'a Timer, layout_state: &'a LayoutState) -> Update<'a> {
+    let method = timer.current_timing_method();
+    let comparison = timer.current_comparison();
+    let split_index = timer.current_split_index();
+    let segments = timer.run().segments();
+
+    Update {
+        phase: match timer.current_phase() {
+            TimerPhase::NotRunning => "NotRunning",
+            TimerPhase::Running => "Running",
+            TimerPhase::Ended => "Ended",
+            TimerPhase::Paused => "Paused",
+        },
+        split_index,
+        current_split: split_index
+            .and_then(|index| segments.get(index))
+            .map(|segment| segment.name()),
+        timing_method: match method {
+            livesplit_core::TimingMethod::RealTime => "RealTime",
+            livesplit_core::TimingMethod::GameTime => "GameTime",
+        },
+        comparison,
+        deltas: segments
+            .iter()
+            .take(split_index.unwrap_or_default())
+            .map(|segment| {
+                let split_time = segment.split_time()[method]?;
+                let comparison = segment.comparison(comparison)[method]?;
+                Some((split_time - comparison).total_seconds())
+            })
+            .collect(),
+        layout: layout_state,
+    }
+}
+
+pub fn start(address: SocketAddr, clients: Clients, sink: ExtEventSink) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    log::info!("WebSocket server listening on {}", address);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let updates = clients.connect();
+                    let sink = sink.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = handle_connection(stream, updates, &sink) {
+                            log::warn!("WebSocket connection closed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => log::warn!("Failed to accept WebSocket connection: {}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    updates: Receiver<Arc<str>>,
+    sink: &ExtEventSink,
+) -> tungstenite::Result<()> {
+    let mut socket = tungstenite::accept(stream).map_err(|e| match e {
+        tungstenite::HandshakeError::Failure(e) => e,
+        tungstenite::HandshakeError::Interrupted(_) => {
+            tungstenite::Error::Io(io::Error::from(ErrorKind::WouldBlock))
+        }
+    })?;
+    // The socket is polled for commands in between sending the updates.
+    socket.get_mut().set_read_timeout(Some(POLL_INTERVAL))?;
+
+    loop {
+        match socket.read_message() {
+            Ok(Message::Text(text)) => handle_command(&text, sink),
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        send_updates(&mut socket, &updates)?;
+    }
+}
+
+fn send_updates(
+    socket: &mut WebSocket<TcpStream>,
+    updates: &Receiver<Arc<str>>,
+) -> tungstenite::Result<()> {
+    // Only the most recent update is of interest to the client.
+    if let Some(update) = updates.try_iter().last() {
+        socket.write_message(Message::Text(update.to_string()))?;
+    }
+    Ok(())
+}
+
+fn handle_command(text: &str, sink: &ExtEventSink) {
+    let command = match serde_json::from_str(text) {
+        Ok(command) => command,
+        Err(e) => {
+            log::warn!("Invalid WebSocket command: {}", e);
+            return;
+        }
+    };
+
+    let _ = match command {
+        Command::StartOrSplit => sink.submit_command(CONTEXT_MENU_START_OR_SPLIT, (), Target::Auto),
+        Command::Reset => sink.submit_command(CONTEXT_MENU_RESET, (), Target::Auto),
+        Command::UndoSplit => sink.submit_command(CONTEXT_MENU_UNDO_SPLIT, (), Target::Auto),
+        Command::SkipSplit => sink.submit_command(CONTEXT_MENU_SKIP_SPLIT, (), Target::Auto),
+        Command::TogglePause => sink.submit_command(CONTEXT_MENU_TOGGLE_PAUSE, (), Target::Auto),
+        Command::UndoAllPauses => {
+            sink.submit_command(CONTEXT_MENU_UNDO_ALL_PAUSES, (), Target::Auto)
+        }
+        Command::ToggleTimingMethod => {
+            sink.submit_command(CONTEXT_MENU_TOGGLE_TIMING_METHOD, (), Target::Auto)
+        }
+        Command::SetComparison { comparison } => {
+            sink.submit_command(CONTEXT_MENU_SET_COMPARISON, comparison, Target::Auto)
+        }
+    };
+}